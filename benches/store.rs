@@ -0,0 +1,265 @@
+//! Measures table operations of every backend, with values in each of the built-in formats.
+//!
+//! Benchmarks are named `<backend>/<format>/<operation>/<value size>`, and `<backend>/txn/..` for
+//! the cost of transactions alone, so `cargo bench -- mem/postcard` runs a subset.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use stor::config::TableConfig;
+use stor::format::{DFormat, EFormat};
+use stor::types::{BigEndian, OwnedType, Str};
+use stor::{Store, Transaction};
+
+/// Entries in every table before measurements start.
+const ENTRIES: u64 = 10_000;
+/// Entries read by a single range scan.
+const SCAN: usize = 100;
+/// Puts per write transaction, the commit is not measured.
+const BATCH: u64 = 1_000;
+/// Approximate sizes of encoded values.
+const SIZES: [usize; 3] = [16, 256, 4096];
+
+/// Value of the serde based formats, with most of its size in `data`.
+#[cfg(any(
+    feature = "format-json",
+    feature = "format-postcard",
+    feature = "format-ordcode"
+))]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Record {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+    data: Vec<u8>,
+}
+
+#[cfg(any(
+    feature = "format-json",
+    feature = "format-postcard",
+    feature = "format-ordcode"
+))]
+impl Record {
+    fn new(size: usize) -> Self {
+        Record {
+            id: 42,
+            name: "benchmark".to_string(),
+            tags: vec!["a".to_string(), "b".to_string()],
+            data: vec![0xAB; size],
+        }
+    }
+}
+
+/// The same fields as `Record`, as a protobuf message.
+#[cfg(feature = "format-protokit")]
+#[derive(Debug, Default, Clone, PartialEq, protokit::Proto)]
+struct Message {
+    #[field(1, uint64, singular)]
+    id: u64,
+    #[field(2, string, singular)]
+    name: String,
+    #[field(3, string, repeated)]
+    tags: Vec<String>,
+    #[field(4, bytes, singular)]
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "format-protokit")]
+impl Message {
+    fn new(size: usize) -> Self {
+        Message {
+            id: 42,
+            name: "benchmark".to_string(),
+            tags: vec!["a".to_string(), "b".to_string()],
+            data: vec![0xAB; size],
+        }
+    }
+}
+
+/// Keys are spread over the table, so consecutive gets do not hit the same blocks. They are
+/// stored in big endian, so a range starting at a key scans the keys following it.
+fn key(i: u64) -> u64 {
+    i.wrapping_mul(7919) % ENTRIES
+}
+
+fn bench<S, DC, T>(
+    c: &mut Criterion,
+    backend: &str,
+    store: &Arc<S>,
+    format: &str,
+    size: usize,
+    value: &T,
+) where
+    S: Store,
+    S::Error: std::fmt::Debug,
+    DC: for<'a> EFormat<'a, EItem = T> + DFormat,
+    T: ?Sized,
+{
+    let table = store
+        .typed::<BigEndian<u64>, DC>(&format!("{format}_{size}"), &TableConfig::default())
+        .unwrap();
+    store
+        .with_wtx(|wtx| {
+            for i in 0..ENTRIES {
+                table.put(wtx, &i, value)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let mut group = c.benchmark_group(format!("{backend}/{format}"));
+
+    group.throughput(Throughput::Elements(1));
+    group.bench_function(BenchmarkId::new("get", size), |b| {
+        let rtx = store.rtx().unwrap();
+        let mut i = 0;
+        b.iter(|| {
+            i += 1;
+            black_box(table.get(&rtx, &key(i)).unwrap())
+        });
+    });
+    group.bench_function(BenchmarkId::new("put", size), |b| {
+        b.iter_custom(|iters| {
+            let mut elapsed = Duration::ZERO;
+            let mut done = 0;
+            while done < iters {
+                let n = BATCH.min(iters - done);
+                let mut wtx = store.wtx().unwrap();
+                let start = Instant::now();
+                for i in done..done + n {
+                    table.put(&mut wtx, &key(i), value).unwrap();
+                }
+                elapsed += start.elapsed();
+                wtx.commit().unwrap();
+                done += n;
+            }
+            elapsed
+        });
+    });
+
+    group.throughput(Throughput::Elements(SCAN as u64));
+    group.bench_function(BenchmarkId::new("range", size), |b| {
+        let rtx = store.rtx().unwrap();
+        let mut i = 0;
+        b.iter(|| {
+            i += 1;
+            for item in table.range(&rtx, &(key(i)..)).unwrap().take(SCAN) {
+                black_box(item.unwrap());
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn txn<S: Store>(c: &mut Criterion, backend: &str, store: &Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let table = store
+        .typed::<BigEndian<u64>, OwnedType<u64>>("txn", &TableConfig::default())
+        .unwrap();
+
+    let mut group = c.benchmark_group(format!("{backend}/txn"));
+    group.bench_function("with_rtx", |b| {
+        b.iter(|| store.with_rtx(|_| Ok(())).unwrap());
+    });
+    group.bench_function("with_wtx", |b| {
+        b.iter(|| store.with_wtx(|_| Ok(())).unwrap());
+    });
+    group.bench_function("with_wtx/put", |b| {
+        let mut i = 0;
+        b.iter(|| {
+            i += 1;
+            store.with_wtx(|wtx| table.put(wtx, &key(i), &i)).unwrap()
+        });
+    });
+    group.finish();
+}
+
+/// Fixed size values are stored as they are, the size is part of the type.
+fn owned<S: Store, const N: usize>(c: &mut Criterion, backend: &str, store: &Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    bench::<S, OwnedType<[u8; N]>, _>(c, backend, store, "owned", N, &[0xAB; N]);
+}
+
+fn formats<S: Store>(c: &mut Criterion, backend: &str, store: &Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    txn(c, backend, store);
+
+    owned::<S, { SIZES[0] }>(c, backend, store);
+    owned::<S, { SIZES[1] }>(c, backend, store);
+    owned::<S, { SIZES[2] }>(c, backend, store);
+
+    for size in SIZES {
+        bench::<S, Str, _>(c, backend, store, "str", size, "a".repeat(size).as_str());
+
+        #[cfg(feature = "format-json")]
+        bench::<S, stor::types::SerdeJson<Record>, _>(
+            c,
+            backend,
+            store,
+            "json",
+            size,
+            &Record::new(size),
+        );
+        #[cfg(feature = "format-postcard")]
+        bench::<S, stor::types::Postcard<Record>, _>(
+            c,
+            backend,
+            store,
+            "postcard",
+            size,
+            &Record::new(size),
+        );
+        #[cfg(feature = "format-protokit")]
+        bench::<S, stor::types::Protokit<Message>, _>(
+            c,
+            backend,
+            store,
+            "protokit",
+            size,
+            &Message::new(size),
+        );
+        #[cfg(feature = "format-ordcode")]
+        bench::<S, stor::types::Ordcode<Record>, _>(
+            c,
+            backend,
+            store,
+            "ordcode",
+            size,
+            &Record::new(size),
+        );
+    }
+}
+
+fn mem(c: &mut Criterion) {
+    formats(c, "mem", &Arc::new(stor::db::mem::MemStore::new()));
+}
+
+#[cfg(feature = "impl-rocks")]
+fn rocks(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("stor-bench-{}", std::process::id()));
+    let mut opts = rocksdb::Options::default();
+    opts.create_if_missing(true);
+
+    let store = Arc::new(stor::db::rocks::open(&path, &opts, []).unwrap());
+    formats(c, "rocks", &store);
+
+    drop(store);
+    let _ = std::fs::remove_dir_all(path);
+}
+
+criterion_group!(mem_benches, mem);
+#[cfg(feature = "impl-rocks")]
+criterion_group!(rocks_benches, rocks);
+
+#[cfg(feature = "impl-rocks")]
+criterion_main!(mem_benches, rocks_benches);
+#[cfg(not(feature = "impl-rocks"))]
+criterion_main!(mem_benches);
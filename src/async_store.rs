@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+use tokio::task::JoinError;
+
+use crate::format::{DFormat, EFormat};
+use crate::types::ByteSlice;
+use crate::{RtxOf, Store, Table, Typed, WtxOf};
+
+/// Batches of encoded entries sent by the thread reading a range.
+type Batch<E> = Result<Vec<(Vec<u8>, Vec<u8>)>, E>;
+
+fn joined<R>(res: Result<R, JoinError>) -> R {
+    match res {
+        Ok(out) => out,
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(e) => panic!("Blocking store task failed: {e}"),
+        },
+    }
+}
+
+/// Store accessed from async code.
+///
+/// Transactions run on the blocking thread pool of the tokio runtime. A transaction stays on the
+/// thread that started it, so the closures passed to [`AsyncStore::with_rtx`] and
+/// [`AsyncStore::with_wtx`] do all of their work synchronously, and the returned future resolves
+/// once the transaction ends.
+pub struct AsyncStore<S> {
+    store: Arc<S>,
+}
+
+impl<S> Clone for AsyncStore<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<S> AsyncStore<S>
+where
+    S: Store,
+    S::Table: Clone + Send,
+{
+    pub fn new(store: Arc<S>) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> &Arc<S> {
+        &self.store
+    }
+
+    pub async fn with_rtx<R, F>(&self, fun: F) -> Result<R, S::Error>
+    where
+        R: Send + 'static,
+        F: FnOnce(&RtxOf<S>) -> Result<R, S::Error> + Send + 'static,
+    {
+        let store = self.store.clone();
+        joined(tokio::task::spawn_blocking(move || store.with_rtx(fun)).await)
+    }
+
+    pub async fn with_wtx<R, F>(&self, fun: F) -> Result<R, S::Error>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut WtxOf<S>) -> Result<R, S::Error> + Send + 'static,
+    {
+        let store = self.store.clone();
+        joined(tokio::task::spawn_blocking(move || store.with_wtx(fun)).await)
+    }
+
+    /// Streams entries of `table` within `range`, in ascending order of keys.
+    ///
+    /// Entries are read from a single read transaction on a blocking thread, in batches of up to
+    /// `batch` entries. The thread reads at most two batches ahead of the stream, and stops once
+    /// the stream is dropped.
+    pub fn range<'a, KC, DC, R>(
+        &self,
+        table: &Typed<S, KC, DC>,
+        range: &'a R,
+        batch: usize,
+    ) -> RangeStream<KC, DC, S::Error>
+    where
+        KC: EFormat<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.stream::<KC, _, _>(table, range, batch, false)
+    }
+
+    /// Streams entries of `table` within `range`, in descending order of keys. See
+    /// [`AsyncStore::range`].
+    pub fn rev_range<'a, KC, DC, R>(
+        &self,
+        table: &Typed<S, KC, DC>,
+        range: &'a R,
+        batch: usize,
+    ) -> RangeStream<KC, DC, S::Error>
+    where
+        KC: EFormat<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.stream::<KC, _, _>(table, range, batch, true)
+    }
+
+    fn stream<'a, KC, DC, R>(
+        &self,
+        table: &Typed<S, KC, DC>,
+        range: &'a R,
+        batch: usize,
+        rev: bool,
+    ) -> RangeStream<KC, DC, S::Error>
+    where
+        KC: EFormat<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        let start = range.start_bound().map(|k| KC::encode(k).into_owned());
+        let end = range.end_bound().map(|k| KC::encode(k).into_owned());
+        let batch = batch.max(1);
+
+        let (sender, receiver) = mpsc::channel(2);
+        let store = self.store.clone();
+        let table = table.table.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let read = |sender: &mpsc::Sender<Batch<S::Error>>| -> Result<(), S::Error> {
+                let range = (
+                    start.as_ref().map(Vec::as_slice),
+                    end.as_ref().map(Vec::as_slice),
+                );
+                let rtx = store.rtx()?;
+                let mut items: Box<dyn Iterator<Item = Result<_, S::Error>>> = if rev {
+                    Box::new(table.rev_range::<ByteSlice, ByteSlice, _>(&rtx, &range)?)
+                } else {
+                    Box::new(table.range::<ByteSlice, ByteSlice, _>(&rtx, &range)?)
+                };
+
+                loop {
+                    let chunk = items.by_ref().take(batch).collect::<Result<Vec<_>, _>>()?;
+                    if chunk.is_empty() || sender.blocking_send(Ok(chunk)).is_err() {
+                        return Ok(());
+                    }
+                }
+            };
+
+            if let Err(e) = read(&sender) {
+                let _ = sender.blocking_send(Err(e));
+            }
+        });
+
+        RangeStream {
+            receiver,
+            batch: VecDeque::new(),
+            _p: PhantomData,
+        }
+    }
+}
+
+/// Stream of entries of a range, created by [`AsyncStore::range`].
+pub struct RangeStream<KC, DC, E> {
+    receiver: mpsc::Receiver<Batch<E>>,
+    batch: VecDeque<(Vec<u8>, Vec<u8>)>,
+    _p: PhantomData<fn() -> (KC, DC)>,
+}
+
+impl<KC: DFormat, DC: DFormat, E> Stream for RangeStream<KC, DC, E> {
+    type Item = Result<(KC::DItem, DC::DItem), E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some((k, v)) = self.batch.pop_front() {
+                return Poll::Ready(Some(Ok((KC::decode(&k).unwrap(), DC::decode(&v).unwrap()))));
+            }
+
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Ok(batch))) => self.batch = batch.into(),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
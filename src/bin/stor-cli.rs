@@ -0,0 +1,477 @@
+//! Inspects RocksDB databases created through `stor`.
+//!
+//! Read-only commands open the database in read-only mode, so they can run next to the process
+//! that owns it. `compact` and `drop-table` need exclusive access.
+
+use std::fmt::Display;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, Options, DB,
+    DEFAULT_COLUMN_FAMILY_NAME,
+};
+use stor::config::TableConfig;
+use stor::db::rocks::table_options;
+use stor::format::{DFormat, EFormat};
+use stor::merge::{Counter, MergeOperator};
+use stor::types::{BigEndian, OwnedType, SerdeJson, Str};
+
+const USAGE: &str = "\
+Usage: stor-cli <path> <command> [options]
+
+Commands:
+    tables                  List tables
+    count <table>           Count entries of a table
+    get <table> <key>       Print the value stored under a key
+    scan <table>            Print entries in key order
+        --prefix <key>      Only keys starting with the prefix
+        --from <key>        Only keys greater or equal
+        --to <key>          Only keys less than
+        --limit <n>         At most n entries
+    stats [<table>]         Print size estimates of all tables, or of one
+    compact [<table>]       Compact all tables, or one
+    drop-table <table>      Remove a table with all of its entries
+
+Options:
+    --key <format>          Format of keys, `utf8` by default
+    --value <format>        Format of values, `hex` by default
+
+Formats: hex, utf8, json, u16, u32, u64, i32, i64, u128 (native endian), be-u16, be-u32, be-u64,
+be-i32, be-i64, be-u128 (big endian, sign-flipped), counter (values of a table written by merges)";
+
+/// Rendering of keys and values, using the built-in formats of `stor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Hex,
+    Utf8,
+    Json,
+    U16,
+    U32,
+    U64,
+    I32,
+    I64,
+    U128,
+    BeU16,
+    BeU32,
+    BeU64,
+    BeI32,
+    BeI64,
+    BeU128,
+    Counter,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    if text.len() & 1 == 1 {
+        return Err(format!("odd number of hex digits in `{text}`"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|e| format!("`{text}`: {e}")))
+        .collect()
+}
+
+fn show<F: DFormat>(bytes: &[u8]) -> Option<String>
+where
+    F::DItem: Display,
+{
+    F::decode(bytes).map(|item| item.to_string())
+}
+
+fn parse<F, T>(text: &str) -> Result<Vec<u8>, String>
+where
+    F: for<'a> EFormat<'a, EItem = T>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let item: T = text.parse().map_err(|e| format!("`{text}`: {e}"))?;
+    Ok(F::encode(&item).into_owned())
+}
+
+impl Format {
+    fn new(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "hex" => Format::Hex,
+            "utf8" => Format::Utf8,
+            "json" => Format::Json,
+            "u16" => Format::U16,
+            "u32" => Format::U32,
+            "u64" => Format::U64,
+            "i32" => Format::I32,
+            "i64" => Format::I64,
+            "u128" => Format::U128,
+            "be-u16" => Format::BeU16,
+            "be-u32" => Format::BeU32,
+            "be-u64" => Format::BeU64,
+            "be-i32" => Format::BeI32,
+            "be-i64" => Format::BeI64,
+            "be-u128" => Format::BeU128,
+            "counter" => Format::Counter,
+            _ => return Err(format!("unknown format `{name}`")),
+        })
+    }
+
+    /// Renders stored bytes, falling back to hex if they do not match the format.
+    fn render(self, bytes: &[u8]) -> String {
+        let shown = match self {
+            Format::Hex => None,
+            Format::Utf8 => show::<Str>(bytes),
+            Format::Json => show::<SerdeJson<serde_json::Value>>(bytes),
+            Format::U16 => show::<OwnedType<u16>>(bytes),
+            Format::U32 => show::<OwnedType<u32>>(bytes),
+            Format::U64 => show::<OwnedType<u64>>(bytes),
+            Format::I32 => show::<OwnedType<i32>>(bytes),
+            Format::I64 => show::<OwnedType<i64>>(bytes),
+            Format::U128 => show::<OwnedType<u128>>(bytes),
+            Format::BeU16 => show::<BigEndian<u16>>(bytes),
+            Format::BeU32 => show::<BigEndian<u32>>(bytes),
+            Format::BeU64 => show::<BigEndian<u64>>(bytes),
+            Format::BeI32 => show::<BigEndian<i32>>(bytes),
+            Format::BeI64 => show::<BigEndian<i64>>(bytes),
+            Format::BeU128 => show::<BigEndian<u128>>(bytes),
+            Format::Counter => show::<Counter>(bytes),
+        };
+        shown.unwrap_or_else(|| format!("0x{}", hex(bytes)))
+    }
+
+    /// Encodes a key given on the command line.
+    fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Hex => unhex(text),
+            Format::Utf8 => Ok(text.as_bytes().to_vec()),
+            Format::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_str(text).map_err(|e| format!("`{text}`: {e}"))?;
+                Ok(SerdeJson::<serde_json::Value>::encode(&value).into_owned())
+            }
+            Format::U16 => parse::<OwnedType<u16>, u16>(text),
+            Format::U32 => parse::<OwnedType<u32>, u32>(text),
+            Format::U64 => parse::<OwnedType<u64>, u64>(text),
+            Format::I32 => parse::<OwnedType<i32>, i32>(text),
+            Format::I64 => parse::<OwnedType<i64>, i64>(text),
+            Format::U128 => parse::<OwnedType<u128>, u128>(text),
+            Format::BeU16 => parse::<BigEndian<u16>, u16>(text),
+            Format::BeU32 => parse::<BigEndian<u32>, u32>(text),
+            Format::BeU64 => parse::<BigEndian<u64>, u64>(text),
+            Format::BeI32 => parse::<BigEndian<i32>, i32>(text),
+            Format::BeI64 => parse::<BigEndian<i64>, i64>(text),
+            Format::BeU128 => parse::<BigEndian<u128>, u128>(text),
+            Format::Counter => parse::<Counter, i64>(text),
+        }
+    }
+}
+
+struct Args {
+    path: String,
+    command: String,
+    positional: Vec<String>,
+    key: Format,
+    value: Format,
+    prefix: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<usize>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut out = Args {
+            path: String::new(),
+            command: String::new(),
+            positional: vec![],
+            key: Format::Utf8,
+            value: Format::Hex,
+            prefix: None,
+            from: None,
+            to: None,
+            limit: None,
+        };
+
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value of --{flag}"))?;
+            match flag {
+                "key" => out.key = Format::new(&value)?,
+                "value" => out.value = Format::new(&value)?,
+                "prefix" => out.prefix = Some(value),
+                "from" => out.from = Some(value),
+                "to" => out.to = Some(value),
+                "limit" => out.limit = Some(value.parse().map_err(|e| format!("--limit: {e}"))?),
+                _ => return Err(format!("unknown option --{flag}")),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        out.path = positional.next().ok_or("missing database path")?;
+        out.command = positional.next().ok_or("missing command")?;
+        out.positional = positional.collect();
+
+        Ok(out)
+    }
+
+    fn arg(&self, i: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(i)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing {name}"))
+    }
+}
+
+fn open(args: &Args, writable: bool) -> Result<DB, String> {
+    let opts = Options::default();
+    let names = DB::list_cf(&opts, &args.path).map_err(|e| e.to_string())?;
+
+    // Counters may be stored as operands that were not merged yet, which can only be read with
+    // the merge operator registered.
+    let counters = TableConfig {
+        merge: Some(MergeOperator::of::<Counter>()),
+        ..Default::default()
+    };
+    let cfs = names.into_iter().map(|name| {
+        let opts = match args.value {
+            Format::Counter if args.positional.first() == Some(&name) => table_options(&counters),
+            _ => Options::default(),
+        };
+        ColumnFamilyDescriptor::new(name, opts)
+    });
+
+    let db = if writable {
+        DB::open_cf_descriptors(&opts, &args.path, cfs)
+    } else {
+        DB::open_cf_descriptors_read_only(&opts, &args.path, cfs, false)
+    };
+    db.map_err(|e| e.to_string())
+}
+
+fn handle<'d>(db: &'d DB, name: &str) -> Result<&'d ColumnFamily, String> {
+    db.cf_handle(name)
+        .ok_or_else(|| format!("table `{name}` does not exist"))
+}
+
+/// Names of all tables, or just `only` if it exists.
+fn tables(db: &DB, path: &str, only: Option<&str>) -> Result<Vec<String>, String> {
+    if let Some(name) = only {
+        handle(db, name)?;
+        return Ok(vec![name.to_string()]);
+    }
+
+    let mut names = DB::list_cf(&Options::default(), path).map_err(|e| e.to_string())?;
+    names.retain(|name| name != DEFAULT_COLUMN_FAMILY_NAME);
+    Ok(names)
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let writable = matches!(args.command.as_str(), "compact" | "drop-table");
+    let mut db = open(args, writable)?;
+
+    match args.command.as_str() {
+        "tables" => {
+            for name in tables(&db, &args.path, None)? {
+                println!("{name}");
+            }
+        }
+        "count" => {
+            let cf = handle(&db, args.arg(0, "table")?)?;
+            let mut count = 0u64;
+            for item in db.iterator_cf(cf, IteratorMode::Start) {
+                item.map_err(|e| e.to_string())?;
+                count += 1;
+            }
+            println!("{count}");
+        }
+        "get" => {
+            let cf = handle(&db, args.arg(0, "table")?)?;
+            let key = args.key.encode(args.arg(1, "key")?)?;
+            match db.get_pinned_cf(cf, key).map_err(|e| e.to_string())? {
+                Some(value) => println!("{}", args.value.render(&value)),
+                None => return Err("key not found".to_string()),
+            }
+        }
+        "scan" => {
+            let cf = handle(&db, args.arg(0, "table")?)?;
+            let prefix = args
+                .prefix
+                .as_deref()
+                .map(|p| args.key.encode(p))
+                .transpose()?;
+            let from = args
+                .from
+                .as_deref()
+                .map(|k| args.key.encode(k))
+                .transpose()?;
+            let to = args.to.as_deref().map(|k| args.key.encode(k)).transpose()?;
+
+            let start = match (&prefix, &from) {
+                (Some(prefix), Some(from)) => prefix.max(from).clone(),
+                (Some(start), None) | (None, Some(start)) => start.clone(),
+                (None, None) => vec![],
+            };
+            let it = db.iterator_cf(cf, IteratorMode::From(&start, Direction::Forward));
+
+            for item in it.take(args.limit.unwrap_or(usize::MAX)) {
+                let (key, value) = item.map_err(|e| e.to_string())?;
+                if to.as_deref().is_some_and(|to| *key >= *to)
+                    || prefix.as_deref().is_some_and(|p| !key.starts_with(p))
+                {
+                    break;
+                }
+                println!("{}\t{}", args.key.render(&key), args.value.render(&value));
+            }
+        }
+        "stats" => {
+            let props = [
+                ("keys", "rocksdb.estimate-num-keys"),
+                ("live-data", "rocksdb.estimate-live-data-size"),
+                ("sst-files", "rocksdb.total-sst-files-size"),
+                ("memtables", "rocksdb.cur-size-all-mem-tables"),
+            ];
+            println!("table\t{}", props.map(|(name, _)| name).join("\t"));
+            for name in tables(&db, &args.path, args.positional.first().map(String::as_str))? {
+                let cf = handle(&db, &name)?;
+                let values = props
+                    .iter()
+                    .map(|(_, prop)| match db.property_int_value_cf(cf, *prop) {
+                        Ok(Some(value)) => Ok(value.to_string()),
+                        Ok(None) => Ok("-".to_string()),
+                        Err(e) => Err(e.to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                println!("{name}\t{}", values.join("\t"));
+            }
+        }
+        "compact" => {
+            for name in tables(&db, &args.path, args.positional.first().map(String::as_str))? {
+                let cf = handle(&db, &name)?;
+                db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+            }
+        }
+        "drop-table" => {
+            let name = args.arg(0, "table")?;
+            handle(&db, name)?;
+            db.drop_cf(name).map_err(|e| e.to_string())?;
+        }
+        command => return Err(format!("unknown command `{command}`")),
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    if std::env::args().len() == 1 {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("stor-cli: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, String> {
+        Args::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn parse_commands_and_options() {
+        let args = parse("/var/lib/db get users alice").unwrap();
+        assert_eq!(args.path, "/var/lib/db");
+        assert_eq!(args.command, "get");
+        assert_eq!(args.positional, ["users", "alice"]);
+        assert_eq!((args.key, args.value), (Format::Utf8, Format::Hex));
+        assert_eq!((args.prefix, args.limit), (None, None));
+
+        // Options may come anywhere.
+        let args =
+            parse("--key be-u64 db scan --prefix user/ users --limit 10 --value json").unwrap();
+        assert_eq!((args.path.as_str(), args.command.as_str()), ("db", "scan"));
+        assert_eq!(args.positional, ["users"]);
+        assert_eq!((args.key, args.value), (Format::BeU64, Format::Json));
+        assert_eq!(args.prefix.as_deref(), Some("user/"));
+        assert_eq!(args.limit, Some(10));
+        assert_eq!(args.arg(0, "table").unwrap(), "users");
+        assert_eq!(args.arg(1, "key").unwrap_err(), "missing key");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |args| parse(args).err().unwrap();
+        assert_eq!(error("db"), "missing command");
+        assert_eq!(error("--limit 1"), "missing database path");
+        assert_eq!(error("db scan users --limit"), "missing value of --limit");
+        assert!(error("db scan users --limit ten").starts_with("--limit: "));
+        assert_eq!(error("db scan --reverse yes"), "unknown option --reverse");
+        assert_eq!(error("db scan --key u8"), "unknown format `u8`");
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let cases = [
+            ("hex", "0x00ff10"),
+            ("utf8", "user/alice"),
+            ("json", r#"{"name":"alice","tags":[1,2]}"#),
+            ("u16", "65535"),
+            ("u32", "7"),
+            ("u64", "18446744073709551615"),
+            ("i32", "-7"),
+            ("i64", "-9223372036854775808"),
+            ("u128", "340282366920938463463374607431768211455"),
+            ("be-u16", "258"),
+            ("be-u32", "7"),
+            ("be-u64", "1099511627776"),
+            ("be-i32", "-7"),
+            ("be-i64", "-1"),
+            ("be-u128", "1"),
+            ("counter", "-42"),
+        ];
+        for (name, text) in cases {
+            let format = Format::new(name).unwrap();
+            let bytes = format.encode(text).unwrap();
+            assert_eq!(format.render(&bytes), text, "{name}");
+        }
+
+        assert_eq!(Format::BeU16.encode("258").unwrap(), [1, 2]);
+        assert_eq!(
+            Format::BeI32.encode("-1").unwrap(),
+            [0x7F, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn mismatched_bytes_render_as_hex() {
+        assert_eq!(Format::U64.render(&[1, 2, 3]), "0x010203");
+        assert_eq!(Format::Utf8.render(&[0xFF, 0xFE]), "0xfffe");
+        assert_eq!(Format::Json.render(b"{"), "0x7b");
+
+        assert!(Format::Hex.encode("0xabc").is_err());
+        assert!(Format::Hex.encode("zz").is_err());
+        assert!(Format::U16.encode("65536").is_err());
+        assert!(Format::Json.encode("{").is_err());
+    }
+}
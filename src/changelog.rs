@@ -0,0 +1,353 @@
+use std::collections::Bound;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use crate::config::TableConfig;
+use crate::format::{DFormat, EFormat};
+use crate::sequence::Sequence;
+use crate::types::{ByteSlice, OwnedType, Str};
+use crate::{ErrorOf, RangeOf, RevRangeOf, RtxOf, Store, Table, Typed, WtxOf, META_TABLE};
+
+/// Single committed write to a captured table. Keys and values are kept in their stored encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub seq: u64,
+    pub table: String,
+    pub key: Vec<u8>,
+    /// Value before the write, `None` if the key was not present.
+    pub old: Option<Vec<u8>>,
+    /// Value after the write, `None` if the key was deleted.
+    pub new: Option<Vec<u8>>,
+}
+
+impl Change {
+    pub fn decode_key<KC: DFormat>(&self) -> Option<KC::DItem> {
+        KC::decode(&self.key)
+    }
+
+    pub fn decode_old<DC: DFormat>(&self) -> Option<DC::DItem> {
+        self.old.as_deref().and_then(DC::decode)
+    }
+
+    pub fn decode_new<DC: DFormat>(&self) -> Option<DC::DItem> {
+        self.new.as_deref().and_then(DC::decode)
+    }
+
+    fn encode(table: &str, key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>) -> Vec<u8> {
+        let mut out = vec![];
+        for field in [Some(table.as_bytes()), Some(key), old, new] {
+            match field {
+                Some(bytes) => {
+                    out.push(1);
+                    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                    out.extend_from_slice(bytes);
+                }
+                None => out.push(0),
+            }
+        }
+        out
+    }
+
+    fn decode(seq: u64, mut bytes: &[u8]) -> Option<Self> {
+        let mut field = || -> Option<Option<Vec<u8>>> {
+            let (present, rest) = bytes.split_first()?;
+            bytes = rest;
+            if *present == 0 {
+                return Some(None);
+            }
+            let len = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap()) as usize;
+            let data = bytes.get(4..4 + len)?.to_vec();
+            bytes = &bytes[4 + len..];
+            Some(Some(data))
+        };
+
+        Some(Change {
+            seq,
+            table: String::from_utf8(field()??).ok()?,
+            key: field()??,
+            old: field()?,
+            new: field()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ChangeLogError<E> {
+    Store(E),
+    /// The entry of the log under `key` is not a change recorded by [`ChangeLog::record`].
+    Malformed {
+        key: Vec<u8>,
+    },
+}
+
+impl<E> From<E> for ChangeLogError<E> {
+    fn from(e: E) -> Self {
+        ChangeLogError::Store(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ChangeLogError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeLogError::Store(e) => e.fmt(f),
+            ChangeLogError::Malformed { key } => write!(f, "malformed change under key {key:x?}"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ChangeLogError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChangeLogError::Store(e) => Some(e),
+            ChangeLogError::Malformed { .. } => None,
+        }
+    }
+}
+
+/// Ordered log of changes to a selected set of tables.
+///
+/// Writes through [`Captured`] tables are appended to the log within the same transaction.
+/// Sequence numbers start at 1, and are assigned in commit order, so a consumer that has read
+/// all changes up to some number never misses a change committed later. To keep this guarantee
+/// the sequence stays locked until the writing transaction ends, so writers of captured tables
+/// are serialized.
+pub struct ChangeLog<S: Store> {
+    name: String,
+    log: Typed<S, ByteSlice, ByteSlice>,
+    seq: Sequence<S>,
+}
+
+impl<S: Store> ChangeLog<S> {
+    /// Opens the log stored in table `name`, creating it with `cfg` if it does not exist yet.
+    pub fn open(store: &Arc<S>, name: &str, cfg: &TableConfig) -> Result<Self, S::Error> {
+        Ok(Self {
+            name: name.to_string(),
+            log: store.typed(name, cfg)?,
+            seq: Sequence::new(store, &format!("changelog/{name}"))?,
+        })
+    }
+
+    /// Wraps `table`, so that all writes through the result are recorded in this log.
+    pub fn capture<KC, DC>(self: &Arc<Self>, table: Typed<S, KC, DC>) -> Captured<S, KC, DC> {
+        Captured {
+            table,
+            log: self.clone(),
+        }
+    }
+
+    /// Appends a change to the log, and returns its sequence number.
+    pub fn record(
+        &self,
+        txn: &mut WtxOf<S>,
+        table: &str,
+        key: &[u8],
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<u64, S::Error> {
+        let seq = self.seq.next(txn)?;
+        self.log.put(
+            txn,
+            &seq.to_be_bytes(),
+            &Change::encode(table, key, old, new),
+        )?;
+
+        Ok(seq)
+    }
+
+    /// Up to `limit` changes with sequence numbers greater than `after`, in order. Fails with
+    /// [`ChangeLogError::Malformed`] on entries of the log table that are not changes.
+    pub fn read(
+        &self,
+        txn: &RtxOf<S>,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<Change>, ChangeLogError<S::Error>> {
+        let Some(start) = after.checked_add(1) else {
+            return Ok(vec![]);
+        };
+        let start = start.to_be_bytes();
+
+        let mut out = vec![];
+        for item in self
+            .log
+            .range(txn, &(Bound::Included(&start[..]), Bound::Unbounded))?
+            .take(limit)
+        {
+            let (key, data) = item?;
+            let change = <[u8; 8]>::try_from(&key[..])
+                .ok()
+                .and_then(|seq| Change::decode(u64::from_be_bytes(seq), &data));
+            match change {
+                Some(change) => out.push(change),
+                None => return Err(ChangeLogError::Malformed { key }),
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Removes changes with sequence numbers up to `upto`, typically once all consumers have
+    /// processed them.
+    pub fn truncate(&self, txn: &mut WtxOf<S>, upto: u64) -> Result<(), S::Error> {
+        let end = upto.to_be_bytes();
+        let keys = self
+            .log
+            .range(txn, &(Bound::Unbounded, Bound::Included(&end[..])))?
+            .map(|item| item.map(|(k, _)| k))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for key in keys {
+            self.log.delete(txn, &key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Consumer of this log, that keeps its position under `name`.
+    pub fn consumer(self: &Arc<Self>, name: &str) -> Result<Consumer<S>, S::Error> {
+        Ok(Consumer {
+            key: format!("changelog/{}/consumer/{name}", self.name),
+            positions: self
+                .log
+                .table
+                .store()
+                .typed(META_TABLE, &TableConfig::default())?,
+            log: self.clone(),
+        })
+    }
+}
+
+/// Reader of a [`ChangeLog`], that remembers the last change it processed in the [`META_TABLE`].
+pub struct Consumer<S: Store> {
+    log: Arc<ChangeLog<S>>,
+    key: String,
+    positions: Typed<S, Str, OwnedType<u64>>,
+}
+
+impl<S: Store> Consumer<S> {
+    /// Sequence number of the last processed change, 0 if there is none.
+    pub fn position(&self, txn: &RtxOf<S>) -> Result<u64, S::Error> {
+        Ok(self.positions.get(txn, &self.key)?.unwrap_or(0))
+    }
+
+    /// Up to `limit` changes after the current position.
+    pub fn poll(
+        &self,
+        txn: &RtxOf<S>,
+        limit: usize,
+    ) -> Result<Vec<Change>, ChangeLogError<S::Error>> {
+        self.log.read(txn, self.position(txn)?, limit)
+    }
+
+    /// Marks all changes up to `seq` as processed. Committing it in the same transaction as the
+    /// results of processing makes the consumer resume exactly where it stopped.
+    pub fn checkpoint(&self, txn: &mut WtxOf<S>, seq: u64) -> Result<(), S::Error> {
+        self.positions.put(txn, &self.key, &seq)
+    }
+}
+
+/// Table whose writes are recorded in a [`ChangeLog`].
+pub struct Captured<S: Store, KC, DC> {
+    table: Typed<S, KC, DC>,
+    log: Arc<ChangeLog<S>>,
+}
+
+impl<S: Store, KC, DC> Captured<S, KC, DC> {
+    pub fn get<'a>(
+        &self,
+        txn: &RtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        self.table.get(txn, key)
+    }
+
+    pub fn range<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<RangeOf<'txn, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.table.range(txn, range)
+    }
+
+    pub fn rev_range<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<RevRangeOf<'txn, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.table.rev_range(txn, range)
+    }
+
+    pub fn len(&self, txn: &RtxOf<S>) -> Result<usize, ErrorOf<S>> {
+        self.table.len(txn)
+    }
+
+    pub fn put<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let key = KC::encode(key);
+        let data = DC::encode(data);
+        self.write(txn, &key, Some(&data))
+    }
+
+    pub fn delete<'a>(&self, txn: &mut WtxOf<S>, key: &'a KC::EItem) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        self.write(txn, &KC::encode(key), None)
+    }
+
+    /// Removes all entries, recording a deletion of each of them.
+    pub fn clear(&self, txn: &mut WtxOf<S>) -> Result<(), ErrorOf<S>> {
+        let keys = self
+            .table
+            .table
+            .range::<ByteSlice, ByteSlice, _>(txn, &..)?
+            .map(|item| item.map(|(k, _)| k))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for key in keys {
+            self.write(txn, &key, None)?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, txn: &mut WtxOf<S>, key: &[u8], new: Option<&[u8]>) -> Result<(), ErrorOf<S>> {
+        let table = &self.table.table;
+        let old = table.get_for_update::<ByteSlice, ByteSlice>(txn, key)?;
+
+        match new {
+            Some(data) => table.put::<ByteSlice, ByteSlice>(txn, key, data)?,
+            None if old.is_some() => table.delete::<ByteSlice>(txn, key)?,
+            // Deleting a missing key is not a change.
+            None => return Ok(()),
+        }
+
+        self.log
+            .record(txn, table.name(), key, old.as_deref(), new)
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,77 @@
+use std::any::Any;
+use std::sync::{Arc, OnceLock};
+
+use crate::merge::MergeOperator;
+
+/// Backend-neutral configuration of a single table. Every backend maps these settings onto its
+/// own native options, and ignores the ones it has no use for.
+#[derive(Clone, Default)]
+pub struct TableConfig {
+    /// Compression of stored data, `None` keeps the backend default.
+    pub compression: Option<Compression>,
+    /// Bits per key of a bloom filter, used to skip lookups of missing keys.
+    pub bloom_filter_bits: Option<f64>,
+    /// Cache of data blocks. Tables configured with clones of the same cache share it.
+    pub block_cache: Option<BlockCache>,
+    /// Length of key prefixes, enables prefix filtering when scanning by prefix.
+    pub prefix_len: Option<usize>,
+    /// Optimize the table for point lookups, at the expense of range scans.
+    pub point_lookup: bool,
+    /// Expected size of values in bytes, used to size data blocks.
+    pub value_size: Option<usize>,
+    /// Operator combining values with operands written by [`Typed::merge`](crate::Typed::merge).
+    /// It is not persisted, so it has to be configured every time the table is opened. RocksDB
+    /// only applies it to existing tables when the database is opened.
+    pub merge: Option<MergeOperator>,
+    /// Values are stored by [`Expiring`](crate::expiring::Expiring), so backends can remove the
+    /// expired ones in the background. Set automatically by
+    /// [`Expiring::open`](crate::expiring::Expiring::open), but RocksDB only applies it to existing
+    /// tables when the database is opened.
+    pub expiring: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Snappy,
+    Zlib,
+    Lz4,
+    Zstd,
+}
+
+/// Block cache of a fixed capacity, that can be shared between tables.
+#[derive(Clone)]
+pub struct BlockCache {
+    inner: Arc<CacheInner>,
+}
+
+struct CacheInner {
+    capacity: usize,
+    native: OnceLock<Box<dyn Any + Send + Sync>>,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(CacheInner {
+                capacity,
+                native: OnceLock::new(),
+            }),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Backend-specific cache object, created on first use. Backends must always request the same
+    /// type from the same cache.
+    #[allow(dead_code)]
+    pub(crate) fn native<T: Any + Send + Sync>(&self, make: impl FnOnce(usize) -> T) -> &T {
+        self.inner
+            .native
+            .get_or_init(|| Box::new(make(self.inner.capacity)))
+            .downcast_ref()
+            .expect("Block cache used by multiple backends")
+    }
+}
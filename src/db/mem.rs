@@ -0,0 +1,487 @@
+use std::collections::btree_map;
+use std::collections::{BTreeMap, Bound};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, RangeBounds};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread::{self, ThreadId};
+
+use crate::config::TableConfig;
+use crate::format::{DFormat, EFormat};
+use crate::merge::MergeOperator;
+use crate::observe::{Observers, Op, Veto, Write};
+use crate::{ErrorOf, RtxOf, Store, Table, Transaction, WtxOf};
+
+type Map = BTreeMap<Vec<u8>, Vec<u8>>;
+type Data = BTreeMap<String, Arc<Map>>;
+
+/// Purely in-memory store, useful for tests and for running under Miri.
+///
+/// Read transactions work on a snapshot of the committed data. Write transactions are serialized,
+/// and their changes become visible to other transactions only after a successful commit. Tables
+/// can only be dropped or renamed while no write transaction is open.
+#[derive(Default)]
+pub struct MemStore {
+    data: RwLock<Data>,
+    writer: Mutex<()>,
+    /// Thread holding `writer`, so that waiting for it on the same thread fails instead of
+    /// deadlocking.
+    writer_thread: Mutex<Option<ThreadId>>,
+    observers: Observers,
+}
+
+impl MemStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn snapshot(&self) -> Data {
+        self.data.read().unwrap().clone()
+    }
+
+    fn lock_writer(&self) -> Result<WriterLock<'_>, MemError> {
+        let current = thread::current().id();
+        if *self.writer_thread.lock().unwrap() == Some(current) {
+            return Err(MemError::NestedWrite);
+        }
+
+        let guard = self.writer.lock().unwrap();
+        *self.writer_thread.lock().unwrap() = Some(current);
+
+        Ok(WriterLock {
+            store: self,
+            _guard: guard,
+        })
+    }
+}
+
+struct WriterLock<'a> {
+    store: &'a MemStore,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl Drop for WriterLock<'_> {
+    fn drop(&mut self) {
+        // Runs before the guard is released.
+        *self.store.writer_thread.lock().unwrap() = None;
+    }
+}
+
+#[derive(Debug)]
+pub enum MemError {
+    TableNotFound(String),
+    TableExists(String),
+    /// The table was opened without a merge operator.
+    NoMergeOperator(String),
+    /// The merge operator of the table rejected the value or the operand.
+    MergeFailed(String),
+    /// A pre-commit hook rejected the transaction.
+    Vetoed(Veto),
+    /// The operation waits for write transactions to end, but the calling thread holds one.
+    NestedWrite,
+}
+
+impl fmt::Display for MemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemError::TableNotFound(name) => write!(f, "table `{name}` does not exist"),
+            MemError::TableExists(name) => write!(f, "table `{name}` already exists"),
+            MemError::NoMergeOperator(name) => write!(f, "table `{name}` has no merge operator"),
+            MemError::MergeFailed(name) => write!(f, "merge in table `{name}` failed"),
+            MemError::Vetoed(veto) => veto.fmt(f),
+            MemError::NestedWrite => {
+                write!(f, "a write transaction is already open on this thread")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemError {}
+
+impl Store for MemStore {
+    type Error = MemError;
+    type Rtx<'e> = MemTxn;
+    type Wtx<'e> = WMemTxn<'e>;
+    type Table = MemTable;
+
+    const SERIAL_WRITES: bool = true;
+
+    fn table(self: &Arc<Self>, name: &str, cfg: &TableConfig) -> Result<Self::Table, Self::Error> {
+        self.data
+            .write()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default();
+
+        Ok(MemTable {
+            name: Arc::from(name),
+            store: self.clone(),
+            merge: cfg.merge.clone(),
+        })
+    }
+
+    fn list_tables(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(self.data.read().unwrap().keys().cloned().collect())
+    }
+
+    fn drop_table(&self, name: &str) -> Result<(), Self::Error> {
+        let _lock = self.lock_writer()?;
+        match self.data.write().unwrap().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(MemError::TableNotFound(name.to_string())),
+        }
+    }
+
+    fn rename_table(&self, from: &str, to: &str, _: &TableConfig) -> Result<(), Self::Error> {
+        let _lock = self.lock_writer()?;
+        let mut data = self.data.write().unwrap();
+        if data.contains_key(to) {
+            return Err(MemError::TableExists(to.to_string()));
+        }
+        let map = data
+            .remove(from)
+            .ok_or_else(|| MemError::TableNotFound(from.to_string()))?;
+        data.insert(to.to_string(), map);
+
+        Ok(())
+    }
+
+    fn observers(&self) -> &Observers {
+        &self.observers
+    }
+
+    fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
+        Ok(MemTxn {
+            data: self.snapshot(),
+        })
+    }
+
+    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
+        let lock = self.lock_writer()?;
+
+        Ok(WMemTxn {
+            txn: MemTxn {
+                data: self.snapshot(),
+            },
+            store: self,
+            writes: vec![],
+            _lock: lock,
+        })
+    }
+}
+
+pub struct MemTxn {
+    data: Data,
+}
+
+impl MemTxn {
+    fn map(&self, name: &str) -> Option<&Map> {
+        self.data.get(name).map(Arc::as_ref)
+    }
+
+    /// Map of table `name` for writing. Fails if the table was dropped, so that writes through
+    /// stale handles do not recreate it.
+    fn map_mut(&mut self, store: &MemStore, name: &str) -> Result<&mut Map, MemError> {
+        if !self.data.contains_key(name) {
+            // Tables are created without waiting for writers, so the table might have been
+            // created empty after the snapshot was taken.
+            if !store.data.read().unwrap().contains_key(name) {
+                return Err(MemError::TableNotFound(name.to_string()));
+            }
+            self.data.insert(name.to_string(), Default::default());
+        }
+
+        Ok(Arc::make_mut(self.data.get_mut(name).unwrap()))
+    }
+}
+
+impl Transaction<MemStore> for MemTxn {
+    fn commit(self) -> Result<(), ErrorOf<MemStore>> {
+        Ok(())
+    }
+}
+
+pub struct WMemTxn<'a> {
+    txn: MemTxn,
+    store: &'a MemStore,
+    writes: Vec<Write>,
+    _lock: WriterLock<'a>,
+}
+
+impl<'a> Deref for WMemTxn<'a> {
+    type Target = MemTxn;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+impl Transaction<MemStore> for WMemTxn<'_> {
+    fn commit(self) -> Result<(), ErrorOf<MemStore>> {
+        self.store
+            .observers
+            .committing(&self.writes)
+            .map_err(MemError::Vetoed)?;
+
+        let mut data = self.store.data.write().unwrap();
+        for (name, map) in self.txn.data {
+            match data.get(&name) {
+                Some(old) if Arc::ptr_eq(old, &map) => {}
+                _ => {
+                    data.insert(name, map);
+                }
+            }
+        }
+        drop(data);
+
+        // Still holding the writer lock, so observers see commits in order.
+        self.store.observers.committed(&self.writes);
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct MemTable {
+    name: Arc<str>,
+    store: Arc<MemStore>,
+    merge: Option<MergeOperator>,
+}
+
+pub struct Iter<'a, KC: DFormat, DC: DFormat> {
+    it: Option<btree_map::Range<'a, Vec<u8>, Vec<u8>>>,
+    rev: bool,
+    _p: PhantomData<(KC, DC)>,
+}
+
+impl<'a, KC: DFormat, DC: DFormat> Iterator for Iter<'a, KC, DC> {
+    type Item = Result<(KC::DItem, DC::DItem), MemError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let it = self.it.as_mut()?;
+        let (k, v) = if self.rev {
+            it.next_back()?
+        } else {
+            it.next()?
+        };
+
+        Some(Ok((KC::decode(k).unwrap(), DC::decode(v).unwrap())))
+    }
+}
+
+fn encode_bound<'a, KC: EFormat<'a>>(bound: Bound<&'a KC::EItem>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(i) => Bound::Included(KC::encode(i).to_vec()),
+        Bound::Excluded(i) => Bound::Excluded(KC::encode(i).to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// `BTreeMap::range` panics on inverted ranges, while other backends simply return nothing.
+fn is_empty(start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
+    match (start, end) {
+        (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e)) => s > e,
+        _ => false,
+    }
+}
+
+impl MemTable {
+    fn iter<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn MemTxn,
+        range: &'a R,
+        rev: bool,
+    ) -> Iter<'txn, KC, DC>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        let start = encode_bound::<KC>(range.start_bound());
+        let end = encode_bound::<KC>(range.end_bound());
+
+        let it = match txn.map(&self.name) {
+            Some(map) if !is_empty(&start, &end) => Some(map.range((start, end))),
+            _ => None,
+        };
+
+        Iter {
+            it,
+            rev,
+            _p: Default::default(),
+        }
+    }
+}
+
+impl Table for MemTable {
+    type Store = MemStore;
+    type Range<'e, KC: DFormat, DC: DFormat> = Iter<'e, KC, DC>;
+    type RevRange<'e, KC: DFormat, DC: DFormat> = Iter<'e, KC, DC>;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn store(&self) -> &Arc<Self::Store> {
+        &self.store
+    }
+
+    fn get<'a, 'txn, KC, DC>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        let key = KC::encode(key);
+
+        Ok(txn
+            .map(&self.name)
+            .and_then(|m| m.get(key.as_ref()))
+            .and_then(|v| DC::decode(v)))
+    }
+
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        // Writers are serialized by the store, so plain reads can not race.
+        self.get::<KC, DC>(txn, key)
+    }
+
+    fn range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        range: &'a R,
+    ) -> Result<Self::Range<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        Ok(self.iter(txn, range, false))
+    }
+
+    fn rev_range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        range: &'a R,
+    ) -> Result<Self::RevRange<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        Ok(self.iter(txn, range, true))
+    }
+
+    fn len(&self, txn: &RtxOf<Self::Store>) -> Result<usize, ErrorOf<Self::Store>> {
+        Ok(txn.map(&self.name).map(Map::len).unwrap_or(0))
+    }
+
+    fn put<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let k = KC::encode(key).into_owned();
+        let v = DC::encode(data).into_owned();
+        let map = txn.txn.map_mut(txn.store, &self.name)?;
+        txn.store
+            .observers
+            .record(&mut txn.writes, &self.name, &k, || Op::Put(v.clone()));
+        map.insert(k, v);
+
+        Ok(())
+    }
+
+    fn append<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        self.put::<KC, DC>(txn, key, data)
+    }
+
+    fn merge<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        operand: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let merge = self
+            .merge
+            .as_ref()
+            .ok_or_else(|| MemError::NoMergeOperator(self.name.to_string()))?;
+
+        let k = KC::encode(key).into_owned();
+        let operand = DC::encode(operand);
+        let map = txn.txn.map_mut(txn.store, &self.name)?;
+        let merged = merge
+            .merge(
+                map.get(&k).map(Vec::as_slice),
+                &mut Some(&operand[..]).into_iter(),
+            )
+            .ok_or_else(|| MemError::MergeFailed(self.name.to_string()))?;
+        txn.store
+            .observers
+            .record(&mut txn.writes, &self.name, &k, || {
+                Op::Merge(operand.to_vec())
+            });
+        map.insert(k, merged);
+
+        Ok(())
+    }
+
+    fn delete<'a, KC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+    {
+        let k = KC::encode(key);
+        let map = txn.txn.map_mut(txn.store, &self.name)?;
+        txn.store
+            .observers
+            .record(&mut txn.writes, &self.name, &k, || Op::Delete);
+        map.remove(k.as_ref());
+
+        Ok(())
+    }
+
+    fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
+        let map = txn.txn.map_mut(txn.store, &self.name)?;
+        for k in map.keys() {
+            txn.store
+                .observers
+                .record(&mut txn.writes, &self.name, k, || Op::Delete);
+        }
+        map.clear();
+
+        Ok(())
+    }
+}
@@ -1,2 +1,5 @@
 #[cfg(feature = "impl-rocks")]
 pub mod rocks;
+
+#[cfg(feature = "impl-mem")]
+pub mod mem;
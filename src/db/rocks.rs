@@ -1,37 +1,512 @@
-use std::collections::Bound;
+use std::collections::{BTreeMap, Bound, HashMap};
+use std::fmt;
 use std::marker::PhantomData;
 use std::ops::{Deref, RangeBounds};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
+use rocksdb::backup::{BackupEngine, BackupEngineInfo, BackupEngineOptions, RestoreOptions};
 use rocksdb::{
-    BoundColumnFamily, DBIteratorWithThreadMode, Direction, ErrorKind, IteratorMode, MultiThreaded,
-    Options, ReadOptions, TransactionDB,
+    BlockBasedOptions, BoundColumnFamily, Cache, ColumnFamilyDescriptor, CompactionDecision,
+    DBAccess, DBCompressionType, DBIteratorWithThreadMode, DBWithThreadMode, DataBlockIndexType,
+    IteratorMode, MergeOperands, MultiThreaded, Options, ReadOptions, SliceTransform,
+    TransactionDB, TransactionDBOptions, WriteBatch, WriteBatchWithTransaction, DB,
+    DEFAULT_COLUMN_FAMILY_NAME,
 };
 
-use crate::advance_key;
+use crate::config::{Compression, TableConfig};
+use crate::expiring;
 use crate::format::{DFormat, EFormat};
+use crate::observe::{Observers, Op, Veto, Write};
 use crate::types::{ByteSlice, Ignore};
 use crate::{ErrorOf, RtxOf, Store, Table, Transaction, WtxOf};
 
 pub type DBType = TransactionDB<MultiThreaded>;
 
-impl Store for DBType {
-    type Error = rocksdb::Error;
+/// Settings of a column family that only take effect when the database is opened, or the column
+/// family created. The rest of [`TableConfig`] only tunes performance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Fixed {
+    merge: Option<&'static str>,
+    expiring: bool,
+}
+
+impl Fixed {
+    fn of(cfg: &TableConfig) -> Self {
+        Self {
+            merge: cfg.merge.as_ref().map(|merge| merge.name()),
+            expiring: cfg.expiring,
+        }
+    }
+}
+
+/// RocksDB database used as a store.
+pub struct RockStore {
+    db: DBType,
+    observers: Observers,
+    /// Fixed settings of column families, as they were opened or created. Column families of
+    /// databases passed to [`RockStore::new`] are missing until created through the store.
+    fixed: Mutex<HashMap<String, Fixed>>,
+}
+
+impl RockStore {
+    /// Wraps a database opened elsewhere. The settings its column families were opened with are
+    /// unknown, so they are assumed to match the configuration of [`Store::table`].
+    pub fn new(db: DBType) -> Self {
+        Self {
+            db,
+            observers: Observers::default(),
+            fixed: Default::default(),
+        }
+    }
+
+    /// Fails if `cfg` requests fixed settings, that column family `name` was not opened with.
+    fn check_fixed(&self, name: &str, cfg: &TableConfig) -> Result<(), RockError> {
+        let Some(live) = self.fixed.lock().unwrap().get(name).copied() else {
+            return Ok(());
+        };
+        let requested = Fixed::of(cfg);
+
+        if requested.merge.is_some() && requested.merge != live.merge {
+            return Err(RockError::Unconfigured {
+                table: name.to_string(),
+                setting: "merge operator",
+            });
+        }
+        if requested.expiring && !live.expiring {
+            return Err(RockError::Unconfigured {
+                table: name.to_string(),
+                setting: "removal of expired entries",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl From<DBType> for RockStore {
+    fn from(db: DBType) -> Self {
+        Self::new(db)
+    }
+}
+
+impl Deref for RockStore {
+    type Target = DBType;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+#[derive(Debug)]
+pub enum RockError {
+    Db(rocksdb::Error),
+    /// A pre-commit hook rejected the transaction.
+    Vetoed(Veto),
+    /// The store was opened read-only, or the table does not exist in it.
+    ReadOnly,
+    /// The table exists, but was opened without a setting requested by its configuration, which
+    /// can only be applied by passing the configuration to [`open`].
+    Unconfigured {
+        table: String,
+        setting: &'static str,
+    },
+    /// The table was opened without a merge operator.
+    NoMergeOperator(String),
+}
+
+impl From<rocksdb::Error> for RockError {
+    fn from(e: rocksdb::Error) -> Self {
+        RockError::Db(e)
+    }
+}
+
+impl fmt::Display for RockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RockError::Db(e) => e.fmt(f),
+            RockError::Vetoed(veto) => veto.fmt(f),
+            RockError::ReadOnly => write!(f, "store is read-only"),
+            RockError::Unconfigured { table, setting } => write!(
+                f,
+                "table `{table}` was opened without the requested {setting}, pass its \
+                 configuration to `open`"
+            ),
+            RockError::NoMergeOperator(table) => {
+                write!(f, "table `{table}` has no merge operator")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RockError::Db(e) => Some(e),
+            RockError::Vetoed(_)
+            | RockError::ReadOnly
+            | RockError::Unconfigured { .. }
+            | RockError::NoMergeOperator(_) => None,
+        }
+    }
+}
+
+/// Maps the backend-neutral table configuration onto column family options.
+pub fn table_options(cfg: &TableConfig) -> Options {
+    let mut opts = Options::default();
+    let mut block = BlockBasedOptions::default();
+
+    if let Some(compression) = cfg.compression {
+        opts.set_compression_type(match compression {
+            Compression::None => DBCompressionType::None,
+            Compression::Snappy => DBCompressionType::Snappy,
+            Compression::Zlib => DBCompressionType::Zlib,
+            Compression::Lz4 => DBCompressionType::Lz4,
+            Compression::Zstd => DBCompressionType::Zstd,
+        });
+    }
+
+    let bloom_bits = match (cfg.bloom_filter_bits, cfg.point_lookup) {
+        (None, true) => Some(10.0),
+        (bits, _) => bits,
+    };
+    if let Some(bits) = bloom_bits {
+        block.set_bloom_filter(bits, false);
+    }
+
+    if let Some(cache) = &cfg.block_cache {
+        block.set_block_cache(cache.native(Cache::new_lru_cache));
+    }
+
+    if let Some(len) = cfg.prefix_len {
+        opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(len));
+        opts.set_memtable_prefix_bloom_ratio(0.1);
+    }
+
+    if cfg.point_lookup {
+        block.set_data_block_index_type(DataBlockIndexType::BinaryAndHash);
+        opts.set_memtable_whole_key_filtering(true);
+    }
+
+    // Keep a few values in every block, the default block size is 4KiB.
+    if let Some(size) = cfg.value_size {
+        block.set_block_size(
+            size.saturating_mul(4)
+                .clamp(4 << 10, 1 << 20)
+                .next_power_of_two(),
+        );
+    }
+
+    if let Some(merge) = &cfg.merge {
+        let name = merge.name();
+        let merge = merge.clone();
+        opts.set_merge_operator_associative(
+            name,
+            move |_: &[u8], existing: Option<&[u8]>, operands: &MergeOperands| {
+                merge.merge(existing, &mut operands.iter())
+            },
+        );
+    }
+
+    if cfg.expiring {
+        opts.set_compaction_filter("stor_expiring", |_: u32, _: &[u8], value: &[u8]| {
+            if expiring::split(value).0 <= expiring::now() {
+                CompactionDecision::Remove
+            } else {
+                CompactionDecision::Keep
+            }
+        });
+    }
+
+    opts.set_block_based_table_factory(&block);
+    opts
+}
+
+/// Descriptors of the `existing` column families, and of `tables` if they are to be `created`.
+/// Tables are configured by their own configuration, the rest by default options.
+fn descriptors<'t, I>(existing: Vec<String>, tables: I, create: bool) -> Vec<ColumnFamilyDescriptor>
+where
+    I: IntoIterator<Item = (&'t str, &'t TableConfig)>,
+{
+    let mut cfs = tables
+        .into_iter()
+        .filter(|(name, _)| create || existing.iter().any(|e| e == name))
+        .map(|(name, cfg)| (name.to_string(), table_options(cfg)))
+        .collect::<BTreeMap<_, _>>();
+
+    for name in existing {
+        cfs.entry(name).or_default();
+    }
+
+    cfs.into_iter()
+        .map(|(name, opts)| ColumnFamilyDescriptor::new(name, opts))
+        .collect()
+}
+
+/// Opens the database at `path`, along with all of its existing column families.
+///
+/// Tables listed in `tables` are opened, or created, with their own configuration. Other existing
+/// column families are opened with default options, so [`Store::table`] fails for them if its
+/// configuration requires a merge operator, or is [expiring](TableConfig::expiring).
+pub fn open<'t, P, I>(path: P, opts: &Options, tables: I) -> Result<RockStore, rocksdb::Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (&'t str, &'t TableConfig)>,
+{
+    let tables = tables.into_iter().collect::<Vec<_>>();
+
+    // Listing fails when the database does not exist yet. Any other problem is reported by `open`.
+    let existing = DBType::list_cf(opts, &path).unwrap_or_default();
+    let mut fixed = existing
+        .iter()
+        .map(|name| (name.clone(), Fixed::default()))
+        .collect::<HashMap<_, _>>();
+    for (name, cfg) in &tables {
+        fixed.insert(name.to_string(), Fixed::of(cfg));
+    }
+    let cfs = descriptors(existing, tables, true);
+
+    let mut opts = opts.clone();
+    opts.create_missing_column_families(true);
+
+    let store = DBType::open_cf_descriptors(&opts, &TransactionDBOptions::default(), path, cfs)
+        .map(RockStore::new)?;
+    *store.fixed.lock().unwrap() = fixed;
+
+    Ok(store)
+}
+
+/// Size of write batches used when copying data into a checkpoint.
+const CHECKPOINT_BATCH: usize = 4 << 20;
+
+/// Size of write batches used when copying tables.
+const COPY_BATCH: usize = 4 << 20;
+
+impl RockStore {
+    /// Copies all entries of `src`, as of a snapshot, into the column family `dst`. Entries are
+    /// written in batches, so the copy does not need to fit in memory.
+    fn copy_cf(&self, src: &Arc<BoundColumnFamily>, dst: &str) -> Result<(), RockError> {
+        let dst = self.cf_handle(dst).unwrap();
+        let snapshot = self.snapshot();
+
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        for item in snapshot.iterator_cf(src, IteratorMode::Start) {
+            let (key, value) = item?;
+            batch.put_cf(&dst, key, value);
+
+            if batch.size_in_bytes() >= COPY_BATCH {
+                self.write(std::mem::take(&mut batch))?;
+            }
+        }
+        self.write(batch)?;
+
+        Ok(())
+    }
+
+    /// Brings the database at `path` to the state of a consistent snapshot of this one, creating
+    /// it if it does not exist yet. Only entries that differ are written, and all column families
+    /// are flushed afterwards.
+    ///
+    /// The database can be opened with [`open`], like the original one. The data is copied instead
+    /// of hard-linked like by native RocksDB checkpoints, as the `rocksdb` crate only creates those,
+    /// and backups, of plain databases, not of the [`TransactionDB`] behind this store.
+    pub fn checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), RockError> {
+        let names = DBType::list_cf(&Options::default(), self.path())?;
+
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let mut target = DB::open_cf(&opts, &path, &names)?;
+
+        for name in DB::list_cf(&opts, &path)? {
+            if !names.contains(&name) {
+                target.drop_cf(&name)?;
+            }
+        }
+
+        let snapshot = self.snapshot();
+        for name in &names {
+            let src = self.cf_handle(name).unwrap();
+            let dst = target.cf_handle(name).unwrap();
+
+            let mut batch = WriteBatch::default();
+            let mut old = target.iterator_cf(&dst, IteratorMode::Start);
+            let mut next_old = old.next().transpose()?;
+
+            for item in snapshot.iterator_cf(&src, IteratorMode::Start) {
+                let (key, value) = item?;
+
+                while let Some((old_key, _)) = next_old.as_ref().filter(|(k, _)| *k < key) {
+                    batch.delete_cf(&dst, old_key);
+                    next_old = old.next().transpose()?;
+                }
+                match &next_old {
+                    Some((old_key, old_value)) if *old_key == key => {
+                        if *old_value != value {
+                            batch.put_cf(&dst, &key, &value);
+                        }
+                        next_old = old.next().transpose()?;
+                    }
+                    _ => batch.put_cf(&dst, &key, &value),
+                }
+
+                if batch.size_in_bytes() >= CHECKPOINT_BATCH {
+                    target.write(std::mem::take(&mut batch))?;
+                }
+            }
+
+            while let Some((old_key, _)) = next_old {
+                batch.delete_cf(&dst, old_key);
+                next_old = old.next().transpose()?;
+            }
+
+            target.write(batch)?;
+            target.flush_cf(&dst)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a new backup in the backup directory `dir`, and returns its ID.
+    ///
+    /// The backup engine can not back up the live database, for the same reason as native
+    /// checkpoints can not be taken of it. The state of the database is therefore first brought
+    /// into a [checkpoint](RockStore::checkpoint) kept in `dir/checkpoint`, which is then backed up
+    /// by the RocksDB backup engine into `dir/backups`. Only files that changed since the previous
+    /// backup are copied, so backups are incremental.
+    pub fn backup<P: AsRef<Path>>(&self, dir: P) -> Result<u32, RockError> {
+        let dir = dir.as_ref();
+        self.checkpoint(dir.join("checkpoint"))?;
+
+        let names = DB::list_cf(&Options::default(), dir.join("checkpoint"))?;
+        let checkpoint = DB::open_cf(&Options::default(), dir.join("checkpoint"), &names)?;
+
+        let mut engine = backup_engine(dir)?;
+        engine.create_new_backup_flush(&checkpoint, true)?;
+
+        Ok(engine
+            .get_backup_info()
+            .iter()
+            .map(|info| info.backup_id)
+            .max()
+            .unwrap())
+    }
+}
+
+fn backup_engine(dir: &Path) -> Result<BackupEngine, rocksdb::Error> {
+    let opts = BackupEngineOptions::new(dir.join("backups"))?;
+    BackupEngine::open(&opts, &rocksdb::Env::new()?)
+}
+
+/// Backups in the backup directory `dir`, created by [`RockStore::backup`].
+pub fn backups<P: AsRef<Path>>(dir: P) -> Result<Vec<BackupEngineInfo>, rocksdb::Error> {
+    Ok(backup_engine(dir.as_ref())?.get_backup_info())
+}
+
+/// Removes all but the latest `keep` backups from the backup directory `dir`.
+pub fn purge_backups<P: AsRef<Path>>(dir: P, keep: usize) -> Result<(), rocksdb::Error> {
+    backup_engine(dir.as_ref())?.purge_old_backups(keep)
+}
+
+/// Restores the backup `id` from the backup directory `dir`, or the latest one if `id` is `None`,
+/// into `path`, and opens it like [`open`] does. Any data at `path` is replaced.
+pub fn restore<'t, D, P, I>(
+    dir: D,
+    id: Option<u32>,
+    path: P,
+    opts: &Options,
+    tables: I,
+) -> Result<RockStore, rocksdb::Error>
+where
+    D: AsRef<Path>,
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (&'t str, &'t TableConfig)>,
+{
+    let mut engine = backup_engine(dir.as_ref())?;
+    let restore = RestoreOptions::default();
+    match id {
+        Some(id) => engine.restore_from_backup(&path, &path, &restore, id)?,
+        None => engine.restore_from_latest_backup(&path, &path, &restore)?,
+    }
+
+    open(path, opts, tables)
+}
+
+impl Store for RockStore {
+    type Error = RockError;
     type Rtx<'e> = RockTxn<'e>;
     type Wtx<'e> = WRockTxn<'e>;
-    type Table<'store> = RockTable<'store>;
-    type Config = Options;
-
-    fn table(&self, name: &str, opts: &Self::Config) -> Result<Self::Table<'_>, Self::Error> {
-        match self.create_cf(name, opts) {
-            Ok(..) => {}
-            Err(e)
-                if e.kind() == ErrorKind::InvalidArgument
-                    && e.to_string().contains("Column family already exists") => {}
-            Err(e) => return Err(e),
+    type Table = RockTable;
+
+    /// Opens the table, creating it with `cfg` if it does not exist yet. Fails with
+    /// [`RockError::Unconfigured`] if the table exists, but was not opened with the merge operator
+    /// of `cfg`, or without removal of expired entries while `cfg` is expiring.
+    fn table(self: &Arc<Self>, name: &str, cfg: &TableConfig) -> Result<Self::Table, Self::Error> {
+        let cf = match self.cf_handle(name) {
+            Some(cf) => cf,
+            None => match self.create_cf(name, &table_options(cfg)) {
+                Ok(()) => {
+                    let fixed = Fixed::of(cfg);
+                    self.fixed.lock().unwrap().insert(name.to_string(), fixed);
+                    self.cf_handle(name).unwrap()
+                }
+                // Another thread might have created the column family in the meantime.
+                Err(e) => self.cf_handle(name).ok_or(e)?,
+            },
+        };
+        self.check_fixed(name, cfg)?;
+
+        // SAFETY: The handle only borrows the database, which is kept alive by the `Arc` stored
+        // alongside it in the table, and dropped after it.
+        let cf = unsafe {
+            std::mem::transmute::<Arc<BoundColumnFamily<'_>>, Arc<BoundColumnFamily<'static>>>(cf)
         };
-        let cf = self.cf_handle(name).unwrap();
-        Ok(RockTable { cf })
+
+        Ok(RockTable {
+            cf,
+            name: Arc::from(name),
+            db: self.clone(),
+        })
+    }
+
+    fn list_tables(&self) -> Result<Vec<String>, Self::Error> {
+        let mut names = DBType::list_cf(&Options::default(), self.path())?;
+        names.retain(|name| name != DEFAULT_COLUMN_FAMILY_NAME);
+
+        Ok(names)
+    }
+
+    fn drop_table(&self, name: &str) -> Result<(), Self::Error> {
+        self.drop_cf(name)?;
+        self.fixed.lock().unwrap().remove(name);
+
+        Ok(())
+    }
+
+    fn rename_table(&self, from: &str, to: &str, cfg: &TableConfig) -> Result<(), Self::Error> {
+        let Some(src) = self.cf_handle(from) else {
+            // Produces the same "Invalid column family" error as dropping a missing table.
+            return Ok(self.drop_cf(from)?);
+        };
+        self.create_cf(to, &table_options(cfg))?;
+        self.fixed
+            .lock()
+            .unwrap()
+            .insert(to.to_string(), Fixed::of(cfg));
+
+        // Writes to `from` made during the copy are not carried over, see `Store::rename_table`.
+        if let Err(e) = self.copy_cf(&src, to) {
+            // Do not leave a partial copy behind. The original error is more useful than a
+            // failure to drop it.
+            let _ = self.drop_table(to);
+            return Err(e);
+        }
+
+        self.drop_table(from)
+    }
+
+    fn observers(&self) -> &Observers {
+        &self.observers
     }
 
     fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
@@ -45,12 +520,16 @@ impl Store for DBType {
             db: RockTxn {
                 tx: self.transaction(),
             },
+            store: self,
+            writes: vec![],
         })
     }
 }
 
 pub struct WRockTxn<'a> {
     db: RockTxn<'a>,
+    store: &'a RockStore,
+    writes: Vec<Write>,
 }
 
 impl<'a> Deref for WRockTxn<'a> {
@@ -61,9 +540,16 @@ impl<'a> Deref for WRockTxn<'a> {
     }
 }
 
-impl Transaction<DBType> for WRockTxn<'_> {
-    fn commit(self) -> Result<(), ErrorOf<DBType>> {
-        rocksdb::Transaction::commit(self.db.tx)
+impl Transaction<RockStore> for WRockTxn<'_> {
+    fn commit(self) -> Result<(), ErrorOf<RockStore>> {
+        self.store
+            .observers
+            .committing(&self.writes)
+            .map_err(RockError::Vetoed)?;
+        rocksdb::Transaction::commit(self.db.tx)?;
+        self.store.observers.committed(&self.writes);
+
+        Ok(())
     }
 }
 
@@ -71,28 +557,31 @@ pub struct RockTxn<'a> {
     tx: rocksdb::Transaction<'a, TransactionDB<MultiThreaded>>,
 }
 
-impl Transaction<DBType> for RockTxn<'_> {
-    fn commit(self) -> Result<(), ErrorOf<DBType>> {
-        rocksdb::Transaction::commit(self.tx)
+impl Transaction<RockStore> for RockTxn<'_> {
+    fn commit(self) -> Result<(), ErrorOf<RockStore>> {
+        Ok(rocksdb::Transaction::commit(self.tx)?)
     }
 }
 
 #[derive(Clone)]
-pub struct RockTable<'store> {
-    cf: Arc<BoundColumnFamily<'store>>,
+pub struct RockTable {
+    // Must be declared before `db`, so that the handle is released before the database.
+    cf: Arc<BoundColumnFamily<'static>>,
+    name: Arc<str>,
+    db: Arc<RockStore>,
 }
 
-unsafe impl<'store> Send for RockTable<'store> {}
+unsafe impl Send for RockTable {}
 
-unsafe impl<'store> Sync for RockTable<'store> {}
+unsafe impl Sync for RockTable {}
 
-pub struct Iter<'a, KC: DFormat, DC: DFormat> {
-    it: DBIteratorWithThreadMode<'a, rocksdb::Transaction<'a, DBType>>,
+pub struct Iter<'a, KC: DFormat, DC: DFormat, D: DBAccess = rocksdb::Transaction<'a, DBType>> {
+    it: DBIteratorWithThreadMode<'a, D>,
     _p: PhantomData<(KC, DC)>,
 }
 
-impl<'a, KC: DFormat, DC: DFormat> Iterator for Iter<'a, KC, DC> {
-    type Item = Result<(KC::DItem, DC::DItem), rocksdb::Error>;
+impl<'a, KC: DFormat, DC: DFormat, D: DBAccess> Iterator for Iter<'a, KC, DC, D> {
+    type Item = Result<(KC::DItem, DC::DItem), RockError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.it.next()? {
@@ -100,17 +589,53 @@ impl<'a, KC: DFormat, DC: DFormat> Iterator for Iter<'a, KC, DC> {
                 return Some(Ok((KC::decode(&v.0).unwrap(), DC::decode(&v.1).unwrap())));
             }
             Err(e) => {
-                return Some(Err(e));
+                return Some(Err(e.into()));
             }
         }
     }
 }
 
-impl<'store> Table<'store> for RockTable<'store> {
-    type Store = DBType;
+/// Successor of `key` in the key order, the smallest key greater than it.
+fn successor(key: &[u8]) -> Vec<u8> {
+    [key, &[0]].concat()
+}
+
+/// Read options limiting iteration to keys within `range`.
+fn bounds<'a, KC, R>(range: &'a R) -> ReadOptions
+where
+    KC: EFormat<'a>,
+    R: RangeBounds<KC::EItem>,
+{
+    let mut opt = ReadOptions::default();
+
+    match range.start_bound() {
+        Bound::Included(i) => opt.set_iterate_lower_bound(KC::encode(i)),
+        Bound::Excluded(i) => opt.set_iterate_lower_bound(successor(&KC::encode(i))),
+        Bound::Unbounded => {}
+    }
+
+    match range.end_bound() {
+        Bound::Included(i) => opt.set_iterate_upper_bound(successor(&KC::encode(i))),
+        Bound::Excluded(i) => opt.set_iterate_upper_bound(KC::encode(i)),
+        Bound::Unbounded => {}
+    }
+
+    opt
+}
+
+impl Table for RockTable {
+    type Store = RockStore;
     type Range<'e, KC: DFormat, DC: DFormat> = Iter<'e, KC, DC>;
     type RevRange<'e, KC: DFormat, DC: DFormat> = Iter<'e, KC, DC>;
 
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn store(&self) -> &Arc<Self::Store> {
+        &self.db
+    }
+
     fn get<'a, 'txn, KC, DC>(
         &self,
         txn: &'txn RtxOf<Self::Store>,
@@ -130,6 +655,21 @@ impl<'store> Table<'store> for RockTable<'store> {
         }))
     }
 
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        let key = KC::encode(key);
+        let data = txn.db.tx.get_pinned_for_update_cf(&self.cf, key, true)?;
+
+        Ok(data.and_then(|v| DC::decode(&v)))
+    }
+
     fn range<'a, 'txn, KC, DC, R>(
         &self,
         txn: &'txn RtxOf<Self::Store>,
@@ -140,35 +680,8 @@ impl<'store> Table<'store> for RockTable<'store> {
         DC: DFormat,
         R: RangeBounds<KC::EItem>,
     {
-        let mut opt = ReadOptions::default();
-
-        match range.end_bound() {
-            Bound::Included(i) => {
-                let mut v = KC::encode(i).to_vec();
-                crate::advance_key(&mut v);
-                opt.set_iterate_upper_bound(v);
-            }
-            Bound::Excluded(i) => {
-                opt.set_iterate_upper_bound(KC::encode(i));
-            }
-            _ => {}
-        };
-
-        let it = match range.start_bound() {
-            Bound::Included(i) => {
-                let k = KC::encode(i).to_vec();
-                txn.tx
-                    .iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Forward))
-            }
-            Bound::Excluded(i) => {
-                let mut k = KC::encode(i).to_vec();
-                advance_key(&mut k);
-
-                txn.tx
-                    .iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Forward))
-            }
-            Bound::Unbounded => txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::Start),
-        };
+        let opt = bounds::<KC, R>(range);
+        let it = txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::Start);
 
         Ok(Iter {
             it,
@@ -186,33 +699,8 @@ impl<'store> Table<'store> for RockTable<'store> {
         DC: DFormat,
         R: RangeBounds<KC::EItem>,
     {
-        let mut opt = ReadOptions::default();
-
-        match range.start_bound() {
-            Bound::Included(i) => {
-                let v = KC::encode(i).to_vec();
-                opt.set_iterate_lower_bound(v);
-            }
-            Bound::Excluded(..) => {
-                panic!("Excluded lower bound");
-            }
-            _ => {}
-        };
-
-        let it = match range.end_bound() {
-            Bound::Included(i) => {
-                let k = KC::encode(i);
-                txn.tx
-                    .iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Reverse))
-            }
-            Bound::Excluded(i) => {
-                let mut k = KC::encode(i).to_vec();
-                crate::retreat_key(&mut k);
-                txn.tx
-                    .iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Reverse))
-            }
-            Bound::Unbounded => txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::End),
-        };
+        let opt = bounds::<KC, R>(range);
+        let it = txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::End);
 
         Ok(Iter {
             it,
@@ -221,7 +709,13 @@ impl<'store> Table<'store> for RockTable<'store> {
     }
 
     fn len<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<usize, ErrorOf<Self::Store>> {
-        Ok(txn.tx.iterator(IteratorMode::Start).count())
+        let mut count = 0;
+        for item in txn.tx.iterator_cf(&self.cf, IteratorMode::Start) {
+            item?;
+            count += 1;
+        }
+
+        Ok(count)
     }
 
     fn put<'a, KC, DC>(
@@ -236,7 +730,10 @@ impl<'store> Table<'store> for RockTable<'store> {
     {
         let k = KC::encode(key);
         let v = DC::encode(data);
-        txn.tx.put_cf(&self.cf, k, v)?;
+        txn.store
+            .observers
+            .record(&mut txn.writes, &self.name, &k, || Op::Put(v.to_vec()));
+        txn.db.tx.put_cf(&self.cf, k, v)?;
 
         Ok(())
     }
@@ -254,6 +751,35 @@ impl<'store> Table<'store> for RockTable<'store> {
         self.put::<KC, DC>(txn, key, data)
     }
 
+    fn merge<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        operand: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        // RocksDB accepts merges into column families without an operator, and only fails once
+        // they are read or compacted.
+        let fixed = self.db.fixed.lock().unwrap().get(&*self.name).copied();
+        if fixed.is_some_and(|fixed| fixed.merge.is_none()) {
+            return Err(RockError::NoMergeOperator(self.name.to_string()));
+        }
+
+        let key = KC::encode(key);
+        let operand = DC::encode(operand);
+        txn.store
+            .observers
+            .record(&mut txn.writes, &self.name, &key, || {
+                Op::Merge(operand.to_vec())
+            });
+        txn.db.tx.merge_cf(&self.cf, key, operand)?;
+
+        Ok(())
+    }
+
     fn delete<'a, KC>(
         &self,
         txn: &mut WtxOf<Self::Store>,
@@ -263,7 +789,10 @@ impl<'store> Table<'store> for RockTable<'store> {
         KC: EFormat<'a>,
     {
         let k = KC::encode(key);
-        txn.tx.delete_cf(&self.cf, k)?;
+        txn.store
+            .observers
+            .record(&mut txn.writes, &self.name, &k, || Op::Delete);
+        txn.db.tx.delete_cf(&self.cf, k)?;
         Ok(())
     }
 
@@ -280,3 +809,314 @@ impl<'store> Table<'store> for RockTable<'store> {
         Ok(())
     }
 }
+
+pub type ReadDBType = DBWithThreadMode<MultiThreaded>;
+
+/// RocksDB database opened read-only, or as a secondary instance of a database used by another
+/// process. Write transactions can not be started, [`Store::wtx`] fails with
+/// [`RockError::ReadOnly`].
+///
+/// Reads see the state of the database when it was opened. Secondary instances can catch up with
+/// later changes using [`RockReader::try_catch_up_with_primary`]. Secondary instances do not
+/// support snapshots, so the changes become visible at once, also to read transactions that are
+/// already open. Catch up between transactions where a consistent view matters.
+pub struct RockReader {
+    db: ReadDBType,
+    observers: Observers,
+}
+
+impl Deref for RockReader {
+    type Target = ReadDBType;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+impl RockReader {
+    fn new(db: ReadDBType) -> Self {
+        Self {
+            db,
+            observers: Observers::default(),
+        }
+    }
+
+    /// Applies changes made by the primary instance since the last catch up. Only secondary
+    /// instances can catch up.
+    pub fn try_catch_up_with_primary(&self) -> Result<(), RockError> {
+        Ok(self.db.try_catch_up_with_primary()?)
+    }
+}
+
+/// Opens the existing database at `path` read-only, without taking its lock. Tables listed in
+/// `tables` are opened with their own configuration, which is needed to read merged values.
+pub fn open_read_only<'t, P, I>(
+    path: P,
+    opts: &Options,
+    tables: I,
+) -> Result<RockReader, rocksdb::Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (&'t str, &'t TableConfig)>,
+{
+    let cfs = descriptors(ReadDBType::list_cf(opts, &path)?, tables, false);
+
+    ReadDBType::open_cf_descriptors_read_only(opts, path, cfs, false).map(RockReader::new)
+}
+
+/// Opens the database at `path`, which is in use by another process, as a secondary instance.
+/// The instance keeps its own logs in `secondary_path`. Tables are configured like in
+/// [`open_read_only`].
+pub fn open_secondary<'t, P, Q, I>(
+    path: P,
+    secondary_path: Q,
+    opts: &Options,
+    tables: I,
+) -> Result<RockReader, rocksdb::Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    I: IntoIterator<Item = (&'t str, &'t TableConfig)>,
+{
+    let cfs = descriptors(ReadDBType::list_cf(opts, &path)?, tables, false);
+
+    // Secondary instances must keep all files open, to not miss files deleted by the primary.
+    let mut opts = opts.clone();
+    opts.set_max_open_files(-1);
+
+    ReadDBType::open_cf_descriptors_as_secondary(&opts, path.as_ref(), secondary_path.as_ref(), cfs)
+        .map(RockReader::new)
+}
+
+impl Store for RockReader {
+    type Error = RockError;
+    type Rtx<'e> = ReadTxn<'e>;
+    type Wtx<'e> = WReadTxn<'e>;
+    type Table = ReadTable;
+
+    /// Opens an existing table, `cfg` is ignored. Fails with [`RockError::ReadOnly`] if the table
+    /// does not exist.
+    fn table(self: &Arc<Self>, name: &str, _: &TableConfig) -> Result<Self::Table, Self::Error> {
+        let cf = self.cf_handle(name).ok_or(RockError::ReadOnly)?;
+        // SAFETY: Same as in `RockStore::table`.
+        let cf = unsafe {
+            std::mem::transmute::<Arc<BoundColumnFamily<'_>>, Arc<BoundColumnFamily<'static>>>(cf)
+        };
+
+        Ok(ReadTable {
+            cf,
+            name: Arc::from(name),
+            db: self.clone(),
+        })
+    }
+
+    fn list_tables(&self) -> Result<Vec<String>, Self::Error> {
+        let mut names = ReadDBType::list_cf(&Options::default(), self.path())?;
+        names.retain(|name| name != DEFAULT_COLUMN_FAMILY_NAME);
+
+        Ok(names)
+    }
+
+    fn drop_table(&self, _: &str) -> Result<(), Self::Error> {
+        Err(RockError::ReadOnly)
+    }
+
+    fn rename_table(&self, _: &str, _: &str, _: &TableConfig) -> Result<(), Self::Error> {
+        Err(RockError::ReadOnly)
+    }
+
+    fn observers(&self) -> &Observers {
+        &self.observers
+    }
+
+    fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
+        Ok(ReadTxn { db: &self.db })
+    }
+
+    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
+        Err(RockError::ReadOnly)
+    }
+}
+
+pub struct ReadTxn<'a> {
+    db: &'a ReadDBType,
+}
+
+impl Transaction<RockReader> for ReadTxn<'_> {
+    fn commit(self) -> Result<(), ErrorOf<RockReader>> {
+        Ok(())
+    }
+}
+
+/// Write transaction of a [`RockReader`], which can never be started.
+pub struct WReadTxn<'a> {
+    db: ReadTxn<'a>,
+}
+
+impl<'a> Deref for WReadTxn<'a> {
+    type Target = ReadTxn<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
+
+impl Transaction<RockReader> for WReadTxn<'_> {
+    fn commit(self) -> Result<(), ErrorOf<RockReader>> {
+        Err(RockError::ReadOnly)
+    }
+}
+
+#[derive(Clone)]
+pub struct ReadTable {
+    // Must be declared before `db`, so that the handle is released before the database.
+    cf: Arc<BoundColumnFamily<'static>>,
+    name: Arc<str>,
+    db: Arc<RockReader>,
+}
+
+unsafe impl Send for ReadTable {}
+
+unsafe impl Sync for ReadTable {}
+
+impl Table for ReadTable {
+    type Store = RockReader;
+    type Range<'e, KC: DFormat, DC: DFormat> = Iter<'e, KC, DC, ReadDBType>;
+    type RevRange<'e, KC: DFormat, DC: DFormat> = Iter<'e, KC, DC, ReadDBType>;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn store(&self) -> &Arc<Self::Store> {
+        &self.db
+    }
+
+    fn get<'a, 'txn, KC, DC>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        let data = txn.db.get_pinned_cf(&self.cf, KC::encode(key))?;
+
+        Ok(data.and_then(|v| DC::decode(&v)))
+    }
+
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        self.get::<KC, DC>(txn, key)
+    }
+
+    fn range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        range: &'a R,
+    ) -> Result<Self::Range<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        let opt = bounds::<KC, R>(range);
+        let it = txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::Start);
+
+        Ok(Iter {
+            it,
+            _p: Default::default(),
+        })
+    }
+
+    fn rev_range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        range: &'a R,
+    ) -> Result<Self::RevRange<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        let opt = bounds::<KC, R>(range);
+        let it = txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::End);
+
+        Ok(Iter {
+            it,
+            _p: Default::default(),
+        })
+    }
+
+    fn len(&self, txn: &RtxOf<Self::Store>) -> Result<usize, ErrorOf<Self::Store>> {
+        let mut count = 0;
+        for item in txn.db.iterator_cf(&self.cf, IteratorMode::Start) {
+            item?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn put<'a, KC, DC>(
+        &self,
+        _: &mut WtxOf<Self::Store>,
+        _: &'a KC::EItem,
+        _: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        Err(RockError::ReadOnly)
+    }
+
+    fn append<'a, KC, DC>(
+        &self,
+        _: &mut WtxOf<Self::Store>,
+        _: &'a KC::EItem,
+        _: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        Err(RockError::ReadOnly)
+    }
+
+    fn merge<'a, KC, DC>(
+        &self,
+        _: &mut WtxOf<Self::Store>,
+        _: &'a KC::EItem,
+        _: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        Err(RockError::ReadOnly)
+    }
+
+    fn delete<'a, KC>(
+        &self,
+        _: &mut WtxOf<Self::Store>,
+        _: &'a KC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: EFormat<'a>,
+    {
+        Err(RockError::ReadOnly)
+    }
+
+    fn clear(&self, _: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
+        Err(RockError::ReadOnly)
+    }
+}
@@ -0,0 +1,124 @@
+use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::RangeBounds;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::format::{DFormat, EFormat};
+use crate::{RtxOf, Store, Typed, WtxOf};
+
+#[derive(Debug)]
+pub enum DumpError<E> {
+    Store(E),
+    Io(io::Error),
+    /// Entry on the given line, counted from 1, could not be converted from or to JSON.
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+}
+
+impl<E> From<E> for DumpError<E> {
+    fn from(e: E) -> Self {
+        DumpError::Store(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for DumpError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::Store(e) => e.fmt(f),
+            DumpError::Io(e) => e.fmt(f),
+            DumpError::Json { line, error } => write!(f, "invalid entry on line {line}: {error}"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for DumpError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DumpError::Store(e) => Some(e),
+            DumpError::Io(e) => Some(e),
+            DumpError::Json { error, .. } => Some(error),
+        }
+    }
+}
+
+/// Single line of a dump.
+#[derive(Serialize, Deserialize)]
+struct Entry<K, V> {
+    key: K,
+    value: V,
+}
+
+/// Writes entries of `table` within `range` into `writer` as JSON lines, each an object with
+/// `key` and `value` fields. Returns the number of written entries.
+pub fn dump<'a, S, KC, DC, R>(
+    table: &Typed<S, KC, DC>,
+    txn: &RtxOf<S>,
+    range: &'a R,
+    writer: impl Write,
+) -> Result<u64, DumpError<S::Error>>
+where
+    S: Store,
+    KC: EFormat<'a> + DFormat,
+    DC: DFormat,
+    KC::DItem: Serialize,
+    DC::DItem: Serialize,
+    R: RangeBounds<KC::EItem>,
+{
+    let mut out = BufWriter::new(writer);
+    let mut count = 0u64;
+
+    for item in table.range(txn, range)? {
+        let (key, value) = item?;
+        serde_json::to_writer(&mut out, &Entry { key, value }).map_err(|error| {
+            DumpError::Json {
+                line: count as usize + 1,
+                error,
+            }
+        })?;
+        out.write_all(b"\n").map_err(DumpError::Io)?;
+        count += 1;
+    }
+    out.flush().map_err(DumpError::Io)?;
+
+    Ok(count)
+}
+
+/// Reads JSON lines written by [`dump`] from `reader`, and puts the entries into `table`. Empty
+/// lines are skipped. Returns the number of loaded entries.
+pub fn load<S, KC, DC>(
+    table: &Typed<S, KC, DC>,
+    txn: &mut WtxOf<S>,
+    reader: impl Read,
+) -> Result<u64, DumpError<S::Error>>
+where
+    S: Store,
+    KC: DFormat + for<'a> EFormat<'a>,
+    DC: DFormat + for<'a> EFormat<'a>,
+    KC::DItem: DeserializeOwned + for<'a> Borrow<<KC as EFormat<'a>>::EItem>,
+    DC::DItem: DeserializeOwned + for<'a> Borrow<<DC as EFormat<'a>>::EItem>,
+{
+    let mut count = 0u64;
+
+    for (line, text) in BufReader::new(reader).lines().enumerate() {
+        let text = text.map_err(DumpError::Io)?;
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let entry: Entry<KC::DItem, DC::DItem> =
+            serde_json::from_str(&text).map_err(|error| DumpError::Json {
+                line: line + 1,
+                error,
+            })?;
+        table.put(txn, entry.key.borrow(), entry.value.borrow())?;
+        count += 1;
+    }
+
+    Ok(count)
+}
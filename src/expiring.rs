@@ -0,0 +1,260 @@
+use std::collections::Bound;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::TableConfig;
+use crate::format::{DFormat, EFormat};
+use crate::types::ByteSlice;
+use crate::{ErrorOf, RangeOf, RevRangeOf, RtxOf, Store, Table, WtxOf};
+
+/// Milliseconds since the unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Splits a stored value into its expiry time and the encoded value. Values too short to carry an
+/// expiry time are considered expired.
+pub(crate) fn split(stored: &[u8]) -> (u64, &[u8]) {
+    match stored.get(..8) {
+        Some(at) => (u64::from_be_bytes(at.try_into().unwrap()), &stored[8..]),
+        None => (0, &[]),
+    }
+}
+
+pub type LiveRange<'r, S, KC, DC> = Live<RangeOf<'r, S, KC, ByteSlice>, DC>;
+pub type LiveRevRange<'r, S, KC, DC> = Live<RevRangeOf<'r, S, KC, ByteSlice>, DC>;
+
+/// Table whose entries expire after the duration they were stored with.
+///
+/// Expired entries are invisible immediately, and removed physically by [`Expiring::sweep`]. Tables
+/// configured with [`TableConfig::expiring`](crate::config::TableConfig::expiring) are also swept
+/// in the background by backends that support it, such as RocksDB during compactions.
+pub struct Expiring<S: Store, KC, DC> {
+    table: S::Table,
+    marker: PhantomData<(KC, DC)>,
+}
+
+impl<S: Store, KC, DC> Expiring<S, KC, DC> {
+    /// Opens the table, creating it with `cfg` marked as [expiring](TableConfig::expiring) if it
+    /// does not exist yet. Backends that only apply the setting to existing tables when the store
+    /// is opened, such as RocksDB, fail unless the table was configured as expiring there.
+    pub fn open(store: &Arc<S>, name: &str, cfg: &TableConfig) -> Result<Self, S::Error> {
+        let cfg = TableConfig {
+            expiring: true,
+            ..cfg.clone()
+        };
+
+        Ok(Self {
+            table: store.table(name, &cfg)?,
+            marker: Default::default(),
+        })
+    }
+
+    pub fn get<'a>(
+        &self,
+        txn: &RtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        let now = now();
+        let stored = self.table.get::<KC, ByteSlice>(txn, key)?;
+
+        Ok(stored.and_then(|stored| match split(&stored) {
+            (at, value) if at > now => DC::decode(value),
+            _ => None,
+        }))
+    }
+
+    pub fn range<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<LiveRange<'txn, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        Ok(Live {
+            it: self.table.range::<KC, ByteSlice, R>(txn, range)?,
+            now: now(),
+            _p: PhantomData,
+        })
+    }
+
+    pub fn rev_range<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<LiveRevRange<'txn, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: EFormat<'a> + DFormat,
+        DC: DFormat,
+        R: RangeBounds<KC::EItem>,
+    {
+        Ok(Live {
+            it: self.table.rev_range::<KC, ByteSlice, R>(txn, range)?,
+            now: now(),
+            _p: PhantomData,
+        })
+    }
+
+    /// Stores the value, expiring `ttl` from now.
+    pub fn put<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+        ttl: Duration,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let at = now().saturating_add(ttl.as_millis() as u64);
+        let stored = [&at.to_be_bytes()[..], &DC::encode(data)].concat();
+
+        self.table
+            .put::<ByteSlice, ByteSlice>(txn, &KC::encode(key), &stored)
+    }
+
+    pub fn delete<'a>(&self, txn: &mut WtxOf<S>, key: &'a KC::EItem) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        self.table.delete::<KC>(txn, key)
+    }
+
+    pub fn clear(&self, txn: &mut WtxOf<S>) -> Result<(), ErrorOf<S>> {
+        self.table.clear(txn)
+    }
+
+    /// Examines up to `limit` entries with keys after `from`, and removes the expired ones.
+    /// Returns the key of the last entry examined, to continue from, or `None` once the whole table
+    /// was swept.
+    pub fn sweep(
+        &self,
+        txn: &mut WtxOf<S>,
+        from: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Option<Vec<u8>>, ErrorOf<S>> {
+        let now = now();
+        let limit = limit.max(1);
+        let start = match from {
+            Some(key) => Bound::Excluded(key),
+            None => Bound::Unbounded,
+        };
+
+        let mut examined = 0;
+        let mut last = None;
+        let mut expired = vec![];
+        for item in self
+            .table
+            .range::<ByteSlice, ByteSlice, _>(txn, &(start, Bound::Unbounded))?
+            .take(limit)
+        {
+            let (key, stored) = item?;
+            if split(&stored).0 <= now {
+                expired.push(key.clone());
+            }
+            examined += 1;
+            last = Some(key);
+        }
+
+        for key in &expired {
+            self.table.delete::<ByteSlice>(txn, key)?;
+        }
+
+        Ok(last.filter(|_| examined == limit))
+    }
+
+    /// Sweeps the whole table, in separate write transactions of at most `batch` examined
+    /// entries.
+    pub fn sweep_all(&self, batch: usize) -> Result<(), ErrorOf<S>> {
+        let store = self.table.store();
+        let mut cursor = None;
+        loop {
+            cursor = store.with_wtx(|wtx| self.sweep(wtx, cursor.as_deref(), batch))?;
+            if cursor.is_none() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Starts a thread sweeping the table every `interval`, until the returned handle is dropped.
+    /// Failed sweeps are retried after the next interval.
+    pub fn spawn_sweeper(self: &Arc<Self>, interval: Duration, batch: usize) -> Sweeper
+    where
+        KC: Send + Sync + 'static,
+        DC: Send + Sync + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let table = self.clone();
+
+        let thread = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let _ = table.sweep_all(batch);
+            }
+        });
+
+        Sweeper {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Handle of a background sweeper, stops it when dropped.
+pub struct Sweeper {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Sweeper {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Iterator over entries that did not expire.
+pub struct Live<I, DC> {
+    it: I,
+    now: u64,
+    _p: PhantomData<DC>,
+}
+
+impl<I, K, E, DC> Iterator for Live<I, DC>
+where
+    I: Iterator<Item = Result<(K, Vec<u8>), E>>,
+    DC: DFormat,
+{
+    type Item = Result<(K, DC::DItem), E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.it.next()? {
+                Ok((key, stored)) => match split(&stored) {
+                    (at, value) if at > self.now => {
+                        return Some(Ok((key, DC::decode(value).unwrap())))
+                    }
+                    _ => continue,
+                },
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
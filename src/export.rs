@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::sync::Arc;
+
+use crate::config::TableConfig;
+use crate::types::ByteSlice;
+use crate::{Store, Table, Transaction};
+
+/// Identifies exported files, followed by the version of the format.
+const MAGIC: &[u8; 8] = b"STOR\0EXP";
+const VERSION: u8 = 1;
+
+const TABLE: u8 = 1;
+const ENTRY: u8 = 2;
+const END: u8 = 0;
+
+/// Entries written per write transaction by [`import`].
+const IMPORT_BATCH: usize = 10_000;
+
+#[derive(Debug)]
+pub enum ExportError<E> {
+    Store(E),
+    Io(io::Error),
+    /// The imported file is not an export, or is damaged.
+    Corrupt(&'static str),
+    /// The table to export does not exist.
+    UnknownTable(String),
+}
+
+impl<E> From<E> for ExportError<E> {
+    fn from(e: E) -> Self {
+        ExportError::Store(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ExportError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Store(e) => e.fmt(f),
+            ExportError::Io(e) => e.fmt(f),
+            ExportError::Corrupt(reason) => write!(f, "corrupt export: {reason}"),
+            ExportError::UnknownTable(name) => write!(f, "unknown table `{name}`"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ExportError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExportError::Store(e) => Some(e),
+            ExportError::Io(e) => Some(e),
+            ExportError::Corrupt(_) | ExportError::UnknownTable(_) => None,
+        }
+    }
+}
+
+/// CRC-32 (IEEE) of all bytes passing through a reader or writer.
+struct Crc<T> {
+    inner: T,
+    crc: u32,
+}
+
+impl<T> Crc<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, crc: !0 }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.crc ^= *byte as u32;
+            for _ in 0..8 {
+                self.crc = (self.crc >> 1) ^ (0xEDB8_8320 & (self.crc & 1).wrapping_neg());
+            }
+        }
+    }
+
+    fn sum(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl<W: Write> Write for Crc<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Crc<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut out = [0; N];
+    input.read_exact(&mut out)?;
+    Ok(out)
+}
+
+fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = u32::from_le_bytes(read_array(input)?) as usize;
+    let mut out = vec![];
+    input.take(len as u64).read_to_end(&mut out)?;
+    if out.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(out)
+}
+
+/// Writes all entries of `tables` into `writer`, and returns their number. Fails with
+/// [`ExportError::UnknownTable`] if one of them does not exist.
+///
+/// Entries are read within a single read transaction, so the export is consistent. The format
+/// does not depend on the backend: after a header, every table is written as its name followed by
+/// its entries in key order, each a length-prefixed key and value. The file ends with the number
+/// of entries, and a CRC-32 of everything before it.
+pub fn export<S: Store>(
+    store: &Arc<S>,
+    tables: &[&str],
+    writer: impl Write,
+) -> Result<u64, ExportError<S::Error>> {
+    let existing = store.list_tables()?;
+    if let Some(name) = tables
+        .iter()
+        .find(|name| !existing.iter().any(|e| e == *name))
+    {
+        return Err(ExportError::UnknownTable(name.to_string()));
+    }
+
+    let tables = tables
+        .iter()
+        .map(|name| store.table(name, &TableConfig::default()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = Crc::new(BufWriter::new(writer));
+    let mut count = 0u64;
+
+    let rtx = store.rtx()?;
+    let io = ExportError::Io;
+    out.write_all(MAGIC).map_err(io)?;
+    out.write_all(&[VERSION]).map_err(io)?;
+
+    for table in &tables {
+        out.write_all(&[TABLE]).map_err(io)?;
+        write_bytes(&mut out, table.name().as_bytes()).map_err(io)?;
+
+        for item in table.range::<ByteSlice, ByteSlice, _>(&rtx, &..)? {
+            let (key, value) = item?;
+            out.write_all(&[ENTRY]).map_err(io)?;
+            write_bytes(&mut out, &key).map_err(io)?;
+            write_bytes(&mut out, &value).map_err(io)?;
+            count += 1;
+        }
+    }
+
+    out.write_all(&[END]).map_err(io)?;
+    out.write_all(&count.to_le_bytes()).map_err(io)?;
+    let sum = out.sum();
+    out.write_all(&sum.to_le_bytes()).map_err(io)?;
+    out.flush().map_err(io)?;
+    rtx.commit()?;
+
+    Ok(count)
+}
+
+/// Table that an imported table is loaded into, before it replaces the original.
+fn staging(name: &str) -> String {
+    format!("__stor_import/{name}")
+}
+
+/// Reads an export written by [`export`] into `store`, and returns the number of imported
+/// entries.
+///
+/// Every table in the export replaces the table of the same name, which is created with its
+/// configuration in `tables`, or the default one if it is not listed. Tables are first loaded into
+/// staging tables, in write transactions of a bounded number of entries, and only replace the
+/// originals once the whole file was read and its checksum matches. A damaged file therefore
+/// leaves the tables unchanged. If the process stops while replacing them, the remaining data is
+/// kept in tables named `__stor_import/<table>`. Writes to the imported tables made while the
+/// import runs are lost.
+pub fn import<'t, S, I>(
+    store: &Arc<S>,
+    reader: impl Read,
+    tables: I,
+) -> Result<u64, ExportError<S::Error>>
+where
+    S: Store,
+    I: IntoIterator<Item = (&'t str, &'t TableConfig)>,
+{
+    let configs = tables.into_iter().collect::<HashMap<_, _>>();
+    let default = TableConfig::default();
+    let config = |name: &str| configs.get(name).copied().unwrap_or(&default);
+
+    let mut staged = vec![];
+    let count = match stage(store, reader, &config, &mut staged) {
+        Ok(count) => count,
+        Err(e) => {
+            // The original error is more useful than a failure to clean up.
+            for name in &staged {
+                let _ = store.drop_table(&staging(name));
+            }
+            return Err(e);
+        }
+    };
+
+    let existing = store.list_tables()?;
+    for name in &staged {
+        if existing.contains(name) {
+            store.drop_table(name)?;
+        }
+        store.rename_table(&staging(name), name, config(name))?;
+    }
+
+    Ok(count)
+}
+
+/// Loads the tables of an export into staging tables, and adds their names to `staged`.
+fn stage<'c, S: Store>(
+    store: &Arc<S>,
+    reader: impl Read,
+    config: &dyn Fn(&str) -> &'c TableConfig,
+    staged: &mut Vec<String>,
+) -> Result<u64, ExportError<S::Error>> {
+    let mut input = Crc::new(BufReader::new(reader));
+    let io = ExportError::Io;
+
+    if &read_array::<8>(&mut input).map_err(io)? != MAGIC {
+        return Err(ExportError::Corrupt("not an export"));
+    }
+    if read_array::<1>(&mut input).map_err(io)? != [VERSION] {
+        return Err(ExportError::Corrupt("unsupported version"));
+    }
+
+    let existing = store.list_tables()?;
+    let mut wtx = store.wtx()?;
+    let mut table: Option<S::Table> = None;
+    let mut count = 0u64;
+    let mut pending = 0;
+
+    loop {
+        match read_array::<1>(&mut input).map_err(io)?[0] {
+            TABLE => {
+                let name = String::from_utf8(read_bytes(&mut input).map_err(io)?)
+                    .map_err(|_| ExportError::Corrupt("invalid table name"))?;
+                if staged.contains(&name) {
+                    return Err(ExportError::Corrupt("duplicate table"));
+                }
+
+                // Tables can only be dropped while no write transaction is open.
+                wtx.commit()?;
+                if existing.contains(&staging(&name)) {
+                    store.drop_table(&staging(&name))?;
+                }
+                table = Some(store.table(&staging(&name), config(&name))?);
+                staged.push(name);
+                wtx = store.wtx()?;
+            }
+            ENTRY => {
+                let table = table
+                    .as_ref()
+                    .ok_or(ExportError::Corrupt("entry outside of a table"))?;
+                let key = read_bytes(&mut input).map_err(io)?;
+                let value = read_bytes(&mut input).map_err(io)?;
+                table.put::<ByteSlice, ByteSlice>(&mut wtx, &key, &value)?;
+                count += 1;
+
+                pending += 1;
+                if pending == IMPORT_BATCH {
+                    wtx.commit()?;
+                    wtx = store.wtx()?;
+                    pending = 0;
+                }
+            }
+            END => break,
+            _ => return Err(ExportError::Corrupt("unknown record")),
+        }
+    }
+    wtx.commit()?;
+
+    let expected = u64::from_le_bytes(read_array(&mut input).map_err(io)?);
+    let sum = input.sum();
+    if u32::from_le_bytes(read_array(&mut input).map_err(io)?) != sum {
+        return Err(ExportError::Corrupt("checksum mismatch"));
+    }
+    if expected != count {
+        return Err(ExportError::Corrupt("entry count mismatch"));
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,370 @@
+use std::borrow::Borrow;
+use std::collections::Bound;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use crate::config::TableConfig;
+use crate::format::{DFormat, EFormat};
+use crate::types::ByteSlice;
+use crate::{ErrorOf, RangeOf, RevRangeOf, RtxOf, Store, Table, Typed, WtxOf};
+
+/// Escapes `0x00` bytes and appends a `0x00 0x00` terminator. Escaped keys keep their relative
+/// order, and none of them is a prefix of another, so a primary key can be appended to them.
+pub(crate) fn escape(key: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(key.len() + 2);
+    for &b in key {
+        out.push(b);
+        if b == 0 {
+            out.push(0xFF);
+        }
+    }
+    out.extend_from_slice(&[0, 0]);
+    out
+}
+
+/// Smallest escaped key that sorts after all entries starting with `escaped`.
+pub(crate) fn after(mut escaped: Vec<u8>) -> Vec<u8> {
+    *escaped.last_mut().unwrap() = 1;
+    escaped
+}
+
+#[derive(Debug)]
+pub enum IndexError<E> {
+    Store(E),
+    /// The value maps to the same key of a unique index as the value of another primary key.
+    Unique {
+        index: String,
+    },
+    /// No index of this name was added to the table.
+    UnknownIndex {
+        index: String,
+    },
+}
+
+impl<E> From<E> for IndexError<E> {
+    fn from(e: E) -> Self {
+        IndexError::Store(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for IndexError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Store(e) => e.fmt(f),
+            IndexError::Unique { index } => {
+                write!(f, "value violates the unique constraint of index `{index}`")
+            }
+            IndexError::UnknownIndex { index } => write!(f, "unknown index `{index}`"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for IndexError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IndexError::Store(e) => Some(e),
+            IndexError::Unique { .. } | IndexError::UnknownIndex { .. } => None,
+        }
+    }
+}
+
+/// Result of an operation on an [`Indexed`] table.
+pub type IndexResult<S, T> = Result<T, IndexError<ErrorOf<S>>>;
+
+/// Computes the escaped index key of a value.
+type KeyFn<V> = Box<dyn Fn(&V) -> Vec<u8> + Send + Sync>;
+
+struct Index<S: Store, V> {
+    name: String,
+    table: Typed<S, ByteSlice, ByteSlice>,
+    key: KeyFn<V>,
+    unique: bool,
+}
+
+impl<S: Store, V> Index<S, V> {
+    /// Key of the entry pointing to `pk`. Entries of unique indexes are keyed by the index key
+    /// alone, so that concurrent writers of the same index key conflict with each other.
+    fn entry(&self, escaped: &[u8], pk: &[u8]) -> Vec<u8> {
+        if self.unique {
+            escaped.to_vec()
+        } else {
+            [escaped, pk].concat()
+        }
+    }
+}
+
+/// Table with secondary indexes, that are kept up to date by every `put` and `delete`, within the
+/// same write transaction.
+///
+/// Every index is stored in its own table, mapping index keys to the primary keys of all values
+/// that share them. Keys of unique indexes can be held by a single value at a time.
+pub struct Indexed<S: Store, KC, DC: DFormat> {
+    table: Typed<S, KC, DC>,
+    indexes: Vec<Index<S, DC::DItem>>,
+}
+
+impl<S, KC, DC, V> Indexed<S, KC, DC>
+where
+    S: Store,
+    KC: DFormat,
+    DC: DFormat<DItem = V> + for<'a> EFormat<'a, EItem = V>,
+    V: 'static,
+{
+    pub fn new(table: Typed<S, KC, DC>) -> Self {
+        Self {
+            table,
+            indexes: vec![],
+        }
+    }
+
+    /// Adds an index stored in table `name`, with keys of format `IK` computed from values by `key`.
+    ///
+    /// Existing values are not indexed, add indexes before writing to the table, or use
+    /// [`Indexed::reindex`].
+    pub fn index<IK, K, O, F>(
+        self,
+        store: &Arc<S>,
+        name: &str,
+        cfg: &TableConfig,
+        key: F,
+    ) -> Result<Self, S::Error>
+    where
+        IK: for<'a> EFormat<'a, EItem = K>,
+        K: ?Sized,
+        O: Borrow<K>,
+        F: Fn(&V) -> O + Send + Sync + 'static,
+    {
+        self.add_index::<IK, K, O, F>(store, name, cfg, key, false)
+    }
+
+    /// Adds an index like [`Indexed::index`], that rejects values whose key is already held by
+    /// the value of another primary key.
+    pub fn unique_index<IK, K, O, F>(
+        self,
+        store: &Arc<S>,
+        name: &str,
+        cfg: &TableConfig,
+        key: F,
+    ) -> Result<Self, S::Error>
+    where
+        IK: for<'a> EFormat<'a, EItem = K>,
+        K: ?Sized,
+        O: Borrow<K>,
+        F: Fn(&V) -> O + Send + Sync + 'static,
+    {
+        self.add_index::<IK, K, O, F>(store, name, cfg, key, true)
+    }
+
+    fn add_index<IK, K, O, F>(
+        mut self,
+        store: &Arc<S>,
+        name: &str,
+        cfg: &TableConfig,
+        key: F,
+        unique: bool,
+    ) -> Result<Self, S::Error>
+    where
+        IK: for<'a> EFormat<'a, EItem = K>,
+        K: ?Sized,
+        O: Borrow<K>,
+        F: Fn(&V) -> O + Send + Sync + 'static,
+    {
+        self.indexes.push(Index {
+            name: name.to_string(),
+            table: store.typed(name, cfg)?,
+            key: Box::new(move |v| escape(&IK::encode(key(v).borrow()))),
+            unique,
+        });
+
+        Ok(self)
+    }
+
+    fn find<E>(&self, name: &str) -> Result<&Index<S, V>, IndexError<E>> {
+        self.indexes
+            .iter()
+            .find(|i| i.name == name)
+            .ok_or_else(|| IndexError::UnknownIndex {
+                index: name.to_string(),
+            })
+    }
+
+    pub fn get<'a>(&self, txn: &RtxOf<S>, key: &'a KC::EItem) -> Result<Option<V>, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        self.table.get(txn, key)
+    }
+
+    pub fn range<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<RangeOf<'txn, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.table.range(txn, range)
+    }
+
+    pub fn rev_range<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<RevRangeOf<'txn, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.table.rev_range(txn, range)
+    }
+
+    pub fn len(&self, txn: &RtxOf<S>) -> Result<usize, ErrorOf<S>> {
+        self.table.len(txn)
+    }
+
+    /// Stores the value, and updates all indexes. Fails without writing anything, if the value
+    /// violates the constraint of a unique index.
+    pub fn put<'a>(&self, txn: &mut WtxOf<S>, key: &'a KC::EItem, data: &V) -> IndexResult<S, ()>
+    where
+        KC: EFormat<'a>,
+    {
+        let pk = KC::encode(key);
+        let old = self.table.table.get_for_update::<ByteSlice, DC>(txn, &pk)?;
+
+        // Index keys that changed, as (index, old, new)
+        let mut changed = vec![];
+        for index in &self.indexes {
+            let new = (index.key)(data);
+            let old = old.as_ref().map(|old| (index.key)(old));
+            if old.as_ref() == Some(&new) {
+                continue;
+            }
+
+            if index.unique {
+                let holder = index.table.get_for_update(txn, &new)?;
+                if holder.is_some_and(|holder| holder != *pk) {
+                    return Err(IndexError::Unique {
+                        index: index.name.clone(),
+                    });
+                }
+            }
+            changed.push((index, old, new));
+        }
+
+        for (index, old, new) in changed {
+            if let Some(old) = old {
+                index.table.delete(txn, &index.entry(&old, &pk))?;
+            }
+            index.table.put(txn, &index.entry(&new, &pk), &pk)?;
+        }
+
+        Ok(self.table.table.put::<ByteSlice, DC>(txn, &pk, data)?)
+    }
+
+    pub fn delete<'a>(&self, txn: &mut WtxOf<S>, key: &'a KC::EItem) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        let pk = KC::encode(key);
+        if let Some(old) = self.table.table.get_for_update::<ByteSlice, DC>(txn, &pk)? {
+            for index in &self.indexes {
+                index
+                    .table
+                    .delete(txn, &index.entry(&(index.key)(&old), &pk))?;
+            }
+        }
+
+        self.table.table.delete::<ByteSlice>(txn, &pk)
+    }
+
+    pub fn clear(&self, txn: &mut WtxOf<S>) -> Result<(), ErrorOf<S>> {
+        for index in &self.indexes {
+            index.table.clear(txn)?;
+        }
+        self.table.clear(txn)
+    }
+
+    /// Rebuilds the contents of index `name` from all values of the table. Fails if values of a
+    /// unique index violate its constraint.
+    pub fn reindex(&self, txn: &mut WtxOf<S>, name: &str) -> IndexResult<S, ()> {
+        let index = self.find(name)?;
+        index.table.clear(txn)?;
+
+        let items = self
+            .table
+            .table
+            .range::<ByteSlice, DC, _>(txn, &..)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (pk, value) in items {
+            let entry = index.entry(&(index.key)(&value), &pk);
+            if index.unique && index.table.get(txn, &entry)?.is_some() {
+                return Err(IndexError::Unique {
+                    index: index.name.clone(),
+                });
+            }
+            index.table.put(txn, &entry, &pk)?;
+        }
+
+        Ok(())
+    }
+
+    /// All entries, whose key in index `name` equals `key`.
+    pub fn get_by_index<IK, K>(
+        &self,
+        txn: &RtxOf<S>,
+        name: &str,
+        key: &K,
+    ) -> IndexResult<S, Vec<(KC::DItem, V)>>
+    where
+        IK: for<'a> EFormat<'a, EItem = K>,
+        K: ?Sized,
+    {
+        self.range_by_index::<IK, K, _>(txn, name, &(Bound::Included(key), Bound::Included(key)))
+    }
+
+    /// All entries, whose key in index `name` lies within `range`, ordered by the index key.
+    /// Fails with [`IndexError::UnknownIndex`] if no index `name` was added.
+    pub fn range_by_index<IK, K, R>(
+        &self,
+        txn: &RtxOf<S>,
+        name: &str,
+        range: &R,
+    ) -> IndexResult<S, Vec<(KC::DItem, V)>>
+    where
+        IK: for<'a> EFormat<'a, EItem = K>,
+        K: ?Sized,
+        R: RangeBounds<K>,
+    {
+        let index = self.find(name)?;
+        let escaped = |k: &K| escape(&IK::encode(k));
+
+        let start = match range.start_bound() {
+            Bound::Included(k) => Bound::Included(escaped(k)),
+            Bound::Excluded(k) => Bound::Included(after(escaped(k))),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => Bound::Excluded(after(escaped(k))),
+            Bound::Excluded(k) => Bound::Excluded(escaped(k)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let bounds = (
+            start.as_ref().map(Vec::as_slice),
+            end.as_ref().map(Vec::as_slice),
+        );
+
+        let mut out = vec![];
+        for item in index.table.range(txn, &bounds)? {
+            let (_, pk) = item?;
+            if let Some(value) = self.table.table.get::<ByteSlice, DC>(txn, &pk)? {
+                out.push((KC::decode(&pk).unwrap(), value));
+            }
+        }
+
+        Ok(out)
+    }
+}
@@ -1,39 +1,49 @@
+#[cfg(feature = "async")]
+pub mod async_store;
+pub mod changelog;
+pub mod config;
 pub mod db;
+#[cfg(feature = "format-json")]
+pub mod dump;
+pub mod expiring;
+pub mod export;
 pub mod format;
+pub mod index;
+pub mod merge;
+pub mod migrate;
+pub mod multi;
+pub mod observe;
+pub mod sequence;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod types;
 
+use crate::config::TableConfig;
 use crate::format::{DFormat, EFormat};
+use crate::merge::MergeFormat;
+use crate::multi::MultiTyped;
+use crate::observe::{prefix_end, Observers, Subscription};
+use crate::sequence::Sequence;
+use std::collections::Bound;
 use std::error::Error;
 use std::marker;
-use std::mem::ManuallyDrop;
 use std::ops::{Deref, RangeBounds};
+use std::sync::{Arc, OnceLock};
 
-pub(crate) fn advance_key(bytes: &mut Vec<u8>) {
-    match bytes.last_mut() {
-        Some(&mut 255) | None => bytes.push(0),
-        Some(last) => *last += 1,
-    }
-}
+/// Table reserved for metadata maintained by this crate, such as schema versions.
+pub const META_TABLE: &str = "__stor_meta";
 
-pub(crate) fn retreat_key(bytes: &mut Vec<u8>) {
-    match bytes.last_mut() {
-        Some(&mut 0) => {
-            bytes.pop();
-        }
-        Some(last) => *last -= 1,
-        None => panic!("Vec is empty and must not be"),
-    }
-}
+/// Number of IDs reserved at once by [`Typed::insert_auto`].
+pub const AUTO_BLOCK: u64 = 64;
 
-pub type TableOf<'s, S> = <S as Store>::Table<'s>;
+pub type TableOf<S> = <S as Store>::Table;
 pub type ErrorOf<S> = <S as Store>::Error;
 
 pub type RtxOf<'e, S> = <S as Store>::Rtx<'e>;
 pub type WtxOf<'e, S> = <S as Store>::Wtx<'e>;
 
-pub type RangeOf<'e, 'r, S, KC, DC> = <<S as Store>::Table<'e> as Table<'e>>::Range<'r, KC, DC>;
-pub type RevRangeOf<'e, 'r, S, KC, DC> =
-<<S as Store>::Table<'e> as Table<'e>>::RevRange<'r, KC, DC>;
+pub type RangeOf<'r, S, KC, DC> = <<S as Store>::Table as Table>::Range<'r, KC, DC>;
+pub type RevRangeOf<'r, S, KC, DC> = <<S as Store>::Table as Table>::RevRange<'r, KC, DC>;
 
 pub trait Store: Sized + Send + Sync + 'static {
     type Error: Error + Send + Sync + 'static;
@@ -46,25 +56,78 @@ pub trait Store: Sized + Send + Sync + 'static {
         where
             Self: 'e;
 
-    type Table<'store>: Table<'store, Store=Self> + Send + Sync
-        where
-            Self: 'store;
+    /// Handle to a single table. Tables hold their own reference to the store, so they stay
+    /// valid for as long as they are alive, independently of the handle they were opened from.
+    type Table: Table<Store=Self> + Send + Sync;
 
-    type Config: Default;
+    /// Whether write transactions exclude each other, so that a thread holding one can not open
+    /// another.
+    const SERIAL_WRITES: bool = false;
 
-    fn table(&self, name: &str, cfg: &Self::Config) -> Result<Self::Table<'_>, Self::Error>;
+    /// Opens the table, creating it with `cfg` if it does not exist yet.
+    fn table(self: &Arc<Self>, name: &str, cfg: &TableConfig) -> Result<Self::Table, Self::Error>;
 
     fn typed<KC, DC>(
-        &self,
+        self: &Arc<Self>,
         name: &str,
-        cfg: &Self::Config,
+        cfg: &TableConfig,
     ) -> Result<Typed<Self, KC, DC>, Self::Error> {
         Ok(Typed {
+            table: self.table(name, cfg)?,
+            ids: Default::default(),
+            marker: Default::default(),
+        })
+    }
+
+    /// Opens the table like [`Store::typed`], storing multiple values per key.
+    fn multi_typed<KC, DC>(
+        self: &Arc<Self>,
+        name: &str,
+        cfg: &TableConfig,
+    ) -> Result<MultiTyped<Self, KC, DC>, Self::Error> {
+        Ok(MultiTyped {
             table: self.table(name, cfg)?,
             marker: Default::default(),
         })
     }
 
+    /// Names of all tables present in the store.
+    fn list_tables(&self) -> Result<Vec<String>, Self::Error>;
+
+    /// Removes the table and all of its data. Existing handles to the table must not be used
+    /// afterwards.
+    fn drop_table(&self, name: &str) -> Result<(), Self::Error>;
+
+    /// Moves all data of table `from` into a new table `to`, created with `cfg`, and drops `from`.
+    /// Fails if `from` does not exist, or `to` already exists.
+    ///
+    /// Backends without native renames, such as RocksDB, copy the data in multiple transactions.
+    /// Writes made to `from` while it is being renamed are then lost, so the table must not be
+    /// written until the rename returns.
+    fn rename_table(&self, from: &str, to: &str, cfg: &TableConfig) -> Result<(), Self::Error>;
+
+    /// Observers notified about committed write transactions.
+    fn observers(&self) -> &Observers;
+
+    /// Subscribes to changes of keys within `range` in table `name`, delivered after every commit
+    /// that changes them.
+    fn subscribe<'a, KC, DC, R>(&self, name: &str, range: &'a R) -> Subscription<KC, DC>
+        where
+            KC: EFormat<'a>,
+            R: RangeBounds<KC::EItem>,
+    {
+        let start = range.start_bound().map(|k| KC::encode(k).into_owned());
+        let end = range.end_bound().map(|k| KC::encode(k).into_owned());
+
+        self.observers().subscribe(name, start, end)
+    }
+
+    /// Subscribes to changes of keys in table `name`, whose encoding starts with `prefix`.
+    fn subscribe_prefix<KC, DC>(&self, name: &str, prefix: &[u8]) -> Subscription<KC, DC> {
+        self.observers()
+            .subscribe(name, Bound::Included(prefix.to_vec()), prefix_end(prefix))
+    }
+
     fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error>;
     fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error>;
 
@@ -95,10 +158,8 @@ pub trait Transaction<S: Store>: Sized {
     fn commit(self) -> Result<(), ErrorOf<S>>;
 }
 
-pub trait Table<'store>: 'store {
-    type Store: Store<Table<'store>=Self>
-        where
-            Self: 'store;
+pub trait Table: 'static {
+    type Store: Store<Table=Self>;
 
     type Range<'e, KC: DFormat, DC: DFormat>: Iterator<
         Item=Result<(KC::DItem, DC::DItem), ErrorOf<Self::Store>>,
@@ -108,6 +169,11 @@ pub trait Table<'store>: 'store {
         Item=Result<(KC::DItem, DC::DItem), ErrorOf<Self::Store>>,
     >;
 
+    fn name(&self) -> &str;
+
+    /// Store this table belongs to.
+    fn store(&self) -> &Arc<Self::Store>;
+
     fn get<'a, 'txn, KC, DC>(
         &self,
         txn: &'txn RtxOf<Self::Store>,
@@ -117,6 +183,17 @@ pub trait Table<'store>: 'store {
             KC: EFormat<'a>,
             DC: DFormat;
 
+    /// Reads a value within a write transaction, and locks its key against concurrent writers
+    /// until the transaction ends, so that writes depending on the value can not race.
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+        where
+            KC: EFormat<'a>,
+            DC: DFormat;
+
     fn range<'a, 'txn, KC, DC, R>(
         &self,
         txn: &'txn RtxOf<Self::Store>,
@@ -159,6 +236,17 @@ pub trait Table<'store>: 'store {
             KC: EFormat<'a>,
             DC: EFormat<'a>;
 
+    /// Combines `operand` with the stored value, using the merge operator of the table.
+    fn merge<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        operand: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+        where
+            KC: EFormat<'a>,
+            DC: EFormat<'a>;
+
     fn delete<'a, KC>(
         &self,
         txn: &mut WtxOf<Self::Store>,
@@ -170,24 +258,27 @@ pub trait Table<'store>: 'store {
     fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>>;
 }
 
-pub struct Typed<'s, S: Store + 's, KC, DC> {
-    table: S::Table<'s>,
+pub struct Typed<S: Store, KC, DC> {
+    table: S::Table,
+    /// Sequence of [`Typed::insert_auto`], shared by clones of this handle.
+    ids: Arc<OnceLock<Sequence<S>>>,
     marker: marker::PhantomData<(KC, DC)>,
 }
 
-impl<'s, S: Store, KC, DC> Clone for Typed<'s, S, KC, DC>
+impl<S: Store, KC, DC> Clone for Typed<S, KC, DC>
     where
-        S::Table<'s>: Clone,
+        S::Table: Clone,
 {
     fn clone(&self) -> Self {
         Self {
             table: self.table.clone(),
+            ids: self.ids.clone(),
             marker: Default::default(),
         }
     }
 }
 
-impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
+impl<S: Store, KC, DC> Typed<S, KC, DC> {
     pub fn get<'a, 'txn>(
         &self,
         txn: &'txn RtxOf<S>,
@@ -200,11 +291,23 @@ impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
         self.table.get::<KC, DC>(txn, key)
     }
 
+    pub fn get_for_update<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<S>>
+        where
+            KC: EFormat<'a>,
+            DC: DFormat,
+    {
+        self.table.get_for_update::<KC, DC>(txn, key)
+    }
+
     pub fn range<'a, 'txn, R>(
         &self,
         txn: &'txn RtxOf<S>,
         range: &'a R,
-    ) -> Result<RangeOf<'s, 'txn, S, KC, DC>, ErrorOf<S>>
+    ) -> Result<RangeOf<'txn, S, KC, DC>, ErrorOf<S>>
         where
             KC: EFormat<'a> + DFormat,
             DC: DFormat,
@@ -217,7 +320,7 @@ impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
         &self,
         txn: &'txn RtxOf<S>,
         range: &'a R,
-    ) -> Result<RevRangeOf<'s, 'txn, S, KC, DC>, ErrorOf<S>>
+    ) -> Result<RevRangeOf<'txn, S, KC, DC>, ErrorOf<S>>
         where
             KC: EFormat<'a> + DFormat,
             DC: DFormat,
@@ -256,6 +359,22 @@ impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
         self.table.append::<KC, DC>(txn, key, data)
     }
 
+    /// Combines `operand` with the stored value without reading it, so concurrent writers of the
+    /// same key do not conflict. The table must be configured with the merge operator of `DC`,
+    /// see [`TableConfig::merge`](crate::config::TableConfig::merge).
+    pub fn merge<'a, 'txn>(
+        &self,
+        txn: &'txn mut WtxOf<S>,
+        key: &'a KC::EItem,
+        operand: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<S>>
+        where
+            KC: EFormat<'a>,
+            DC: EFormat<'a> + MergeFormat,
+    {
+        self.table.merge::<KC, DC>(txn, key, operand)
+    }
+
     pub fn delete<'a, 'txn>(&self, txn: &'txn mut WtxOf<S>, key: &'a KC::EItem) -> Result<(), ErrorOf<S>>
         where
             KC: EFormat<'a>,
@@ -267,42 +386,67 @@ impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
         self.table.clear(txn)
     }
 
-    pub fn remap_types<KC2, DC2>(self) -> Typed<'s, S, KC2, DC2> {
+    /// Stores the value under the next ID of the sequence named after this table, and returns the
+    /// ID.
+    ///
+    /// Unless the store serializes write transactions anyway, IDs are taken from blocks of
+    /// [`AUTO_BLOCK`] reserved with [`Sequence::allocate`], so concurrent inserts do not wait for
+    /// each other. IDs are then unique, but neither gapless, nor ordered by commit.
+    pub fn insert_auto<V: ?Sized>(&self, txn: &mut WtxOf<S>, data: &V) -> Result<u64, ErrorOf<S>>
+        where
+            KC: for<'a> EFormat<'a, EItem=u64>,
+            DC: for<'a> EFormat<'a, EItem=V>,
+    {
+        let ids = match self.ids.get() {
+            Some(ids) => ids,
+            None => {
+                let ids = Sequence::new(self.table.store(), self.table.name())?.block_size(AUTO_BLOCK);
+                self.ids.get_or_init(|| ids)
+            }
+        };
+        let id = if S::SERIAL_WRITES { ids.next(txn)? } else { ids.allocate()? };
+        self.table.put::<KC, DC>(txn, &id, data)?;
+
+        Ok(id)
+    }
+
+    pub fn remap_types<KC2, DC2>(self) -> Typed<S, KC2, DC2> {
         Typed {
             table: self.table,
+            ids: self.ids,
             marker: Default::default(),
         }
     }
 
     /// Change the key codec type of this uniform database, specifying the new codec.
-    pub fn remap_key_type<KC2>(self) -> Typed<'s, S, KC2, DC> {
+    pub fn remap_key_type<KC2>(self) -> Typed<S, KC2, DC> {
         self.remap_types::<KC2, DC>()
     }
 
     /// Change the data codec type of this uniform database, specifying the new codec.
-    pub fn remap_data_type<DC2>(self) -> Typed<'s, S, KC, DC2> {
+    pub fn remap_data_type<DC2>(self) -> Typed<S, KC, DC2> {
         self.remap_types::<KC, DC2>()
     }
 }
 
+/// Owning container for a store and the set of tables opened from it.
+///
+/// The store is reference counted, and every table keeps its own handle to it, so neither the
+/// container nor anything cloned out of it can outlive the storage engine.
 pub struct Tables<S: Store, T> {
-    pub store: &'static S,
-    pub table: ManuallyDrop<T>,
+    pub store: Arc<S>,
+    pub table: T,
 }
 
 impl<S: Store, T> Tables<S, T> {
-    pub fn new<F>(store: S, cfg: &S::Config, make: F) -> Result<Tables<S, T>, S::Error>
+    pub fn new<F>(store: S, cfg: &TableConfig, make: F) -> Result<Tables<S, T>, S::Error>
         where
-            F: FnOnce(&'static S, &S::Config) -> Result<T, S::Error>,
+            F: FnOnce(&Arc<S>, &TableConfig) -> Result<T, S::Error>,
     {
-        let store = Box::new(store);
-        let store = Box::leak::<'static>(store) as &'static S;
-        let o = make(&store, cfg)?;
+        let store = Arc::new(store);
+        let table = make(&store, cfg)?;
 
-        Ok(Tables {
-            store,
-            table: ManuallyDrop::new(o),
-        })
+        Ok(Tables { store, table })
     }
 }
 
@@ -310,16 +454,7 @@ impl<S: Store, T> Deref for Tables<S, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        self.table.deref()
-    }
-}
-
-impl<S: Store, T> Drop for Tables<S, T> {
-    fn drop(&mut self) {
-        unsafe {
-            ManuallyDrop::drop(&mut self.table);
-            drop(Box::from_raw(self.store as *const S as *mut S));
-        }
+        &self.table
     }
 }
 
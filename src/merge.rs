@@ -0,0 +1,238 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::format::{DFormat, EFormat};
+
+/// Format of values that can be combined with operands without being read, through
+/// [`Typed::merge`](crate::Typed::merge). Operands are encoded the same way as values.
+///
+/// Backends may combine operands before the value they apply to is known, so merging must be
+/// associative: merging a value with the result of merging some operands must give the same
+/// result as merging it with the operands one by one.
+pub trait MergeFormat: 'static {
+    /// Name the operator is registered under. Backends such as RocksDB persist it, and check it
+    /// when the table is opened again, so it must not change once data was merged.
+    const NAME: &'static str;
+
+    /// Combines the existing value with operands, in the order they were written. Returns `None`
+    /// if any of them is malformed.
+    fn merge<'o>(
+        existing: Option<&'o [u8]>,
+        operands: &mut dyn Iterator<Item = &'o [u8]>,
+    ) -> Option<Vec<u8>>;
+}
+
+type MergeFn = dyn for<'o> Fn(Option<&'o [u8]>, &mut dyn Iterator<Item = &'o [u8]>) -> Option<Vec<u8>>
+    + Send
+    + Sync;
+
+/// Merge operator of a table, registered through
+/// [`TableConfig::merge`](crate::config::TableConfig::merge).
+#[derive(Clone)]
+pub struct MergeOperator {
+    name: &'static str,
+    fun: Arc<MergeFn>,
+}
+
+impl MergeOperator {
+    pub fn of<M: MergeFormat>() -> Self {
+        Self {
+            name: M::NAME,
+            fun: Arc::new(M::merge),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn merge<'o>(
+        &self,
+        existing: Option<&'o [u8]>,
+        operands: &mut dyn Iterator<Item = &'o [u8]>,
+    ) -> Option<Vec<u8>> {
+        (self.fun)(existing, operands)
+    }
+}
+
+/// Signed 64-bit counter, operands are added to the value.
+pub struct Counter;
+
+impl EFormat<'_> for Counter {
+    type EItem = i64;
+
+    fn encode(item: &Self::EItem) -> Cow<'_, [u8]> {
+        Cow::Owned(item.to_le_bytes().to_vec())
+    }
+}
+
+impl DFormat for Counter {
+    type DItem = i64;
+
+    fn decode(bytes: &[u8]) -> Option<Self::DItem> {
+        bytes.try_into().ok().map(i64::from_le_bytes)
+    }
+}
+
+impl MergeFormat for Counter {
+    const NAME: &'static str = "stor.counter";
+
+    fn merge<'o>(
+        existing: Option<&'o [u8]>,
+        operands: &mut dyn Iterator<Item = &'o [u8]>,
+    ) -> Option<Vec<u8>> {
+        let mut sum = 0i64;
+        for item in existing.into_iter().chain(operands) {
+            sum = sum.wrapping_add(Self::decode(item)?);
+        }
+
+        Some(sum.to_le_bytes().to_vec())
+    }
+}
+
+/// Keeps the greatest of the value and operands, encoded using `F`.
+pub struct Max<F>(PhantomData<F>);
+
+impl<'e, F> EFormat<'e> for Max<F>
+where
+    F: EFormat<'e> + 'static,
+{
+    type EItem = F::EItem;
+
+    fn encode(item: &'e Self::EItem) -> Cow<'e, [u8]> {
+        F::encode(item)
+    }
+}
+
+impl<F: DFormat + 'static> DFormat for Max<F> {
+    type DItem = F::DItem;
+
+    fn decode(bytes: &[u8]) -> Option<Self::DItem> {
+        F::decode(bytes)
+    }
+}
+
+impl<F> MergeFormat for Max<F>
+where
+    F: DFormat + 'static,
+    F::DItem: Ord,
+{
+    const NAME: &'static str = "stor.max";
+
+    fn merge<'o>(
+        existing: Option<&'o [u8]>,
+        operands: &mut dyn Iterator<Item = &'o [u8]>,
+    ) -> Option<Vec<u8>> {
+        let mut max: Option<(F::DItem, &[u8])> = None;
+        for bytes in existing.into_iter().chain(operands) {
+            let item = F::decode(bytes)?;
+            if max.as_ref().is_none_or(|(m, _)| item > *m) {
+                max = Some((item, bytes));
+            }
+        }
+
+        max.map(|(_, bytes)| bytes.to_vec())
+    }
+}
+
+/// Encodes a list of items, each prefixed by its length.
+fn encode_list<'a>(items: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut out = vec![];
+    for item in items {
+        out.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+fn decode_list(mut bytes: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut out = vec![];
+    while !bytes.is_empty() {
+        let len = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap()) as usize;
+        out.push(bytes.get(4..4 + len)?);
+        bytes = &bytes[4 + len..];
+    }
+    Some(out)
+}
+
+/// List of items encoded using `F`, operands are appended to the value.
+pub struct Append<F>(PhantomData<F>);
+
+impl<'e, F> EFormat<'e> for Append<F>
+where
+    F: DFormat + for<'a> EFormat<'a> + 'static,
+    for<'a> F::DItem: Borrow<<F as EFormat<'a>>::EItem>,
+{
+    type EItem = [F::DItem];
+
+    fn encode(items: &'e Self::EItem) -> Cow<'e, [u8]> {
+        let items = items.iter().map(|i| F::encode(i.borrow())).collect::<Vec<_>>();
+        Cow::Owned(encode_list(items.iter().map(|i| i.as_ref())))
+    }
+}
+
+impl<F: DFormat + 'static> DFormat for Append<F> {
+    type DItem = Vec<F::DItem>;
+
+    fn decode(bytes: &[u8]) -> Option<Self::DItem> {
+        decode_list(bytes)?.into_iter().map(F::decode).collect()
+    }
+}
+
+impl<F: 'static> MergeFormat for Append<F> {
+    const NAME: &'static str = "stor.append";
+
+    fn merge<'o>(
+        existing: Option<&'o [u8]>,
+        operands: &mut dyn Iterator<Item = &'o [u8]>,
+    ) -> Option<Vec<u8>> {
+        let mut out = existing.unwrap_or_default().to_vec();
+        for operand in operands {
+            decode_list(operand)?;
+            out.extend_from_slice(operand);
+        }
+        Some(out)
+    }
+}
+
+/// Set of distinct items encoded using `F`, ordered by their encoding. Operands are added to
+/// the value.
+pub struct SetUnion<F>(PhantomData<F>);
+
+impl<'e, F> EFormat<'e> for SetUnion<F>
+where
+    F: DFormat + for<'a> EFormat<'a> + 'static,
+    for<'a> F::DItem: Borrow<<F as EFormat<'a>>::EItem>,
+{
+    type EItem = [F::DItem];
+
+    fn encode(items: &'e Self::EItem) -> Cow<'e, [u8]> {
+        let items = items.iter().map(|i| F::encode(i.borrow())).collect::<BTreeSet<_>>();
+        Cow::Owned(encode_list(items.iter().map(|i| i.as_ref())))
+    }
+}
+
+impl<F: DFormat + 'static> DFormat for SetUnion<F> {
+    type DItem = Vec<F::DItem>;
+
+    fn decode(bytes: &[u8]) -> Option<Self::DItem> {
+        decode_list(bytes)?.into_iter().map(F::decode).collect()
+    }
+}
+
+impl<F: 'static> MergeFormat for SetUnion<F> {
+    const NAME: &'static str = "stor.set_union";
+
+    fn merge<'o>(
+        existing: Option<&'o [u8]>,
+        operands: &mut dyn Iterator<Item = &'o [u8]>,
+    ) -> Option<Vec<u8>> {
+        let mut items = BTreeSet::new();
+        for set in existing.into_iter().chain(operands) {
+            items.extend(decode_list(set)?);
+        }
+        Some(encode_list(items.into_iter()))
+    }
+}
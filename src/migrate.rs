@@ -0,0 +1,282 @@
+use std::collections::Bound;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::TableConfig;
+use crate::format::{DFormat, EFormat};
+use crate::types::{ByteSlice, OwnedType, Str};
+use crate::{paged, Store, Table, Tables, Transaction, Typed, WtxOf, META_TABLE};
+
+/// Position within a partially finished migration step. Empty when the step starts.
+pub type Checkpoint = Option<Vec<u8>>;
+
+type Step<S> = Box<
+    dyn Fn(&Arc<S>, &mut WtxOf<S>, &mut Checkpoint) -> Result<(), <S as Store>::Error>
+        + Send
+        + Sync,
+>;
+
+#[derive(Debug)]
+pub enum MigrationError<E> {
+    Store(E),
+    /// The stored schema version is newer than the registered migrations.
+    UnknownVersion {
+        stored: u64,
+        known: u64,
+    },
+    /// A value being converted is encoded in neither the old nor the new format.
+    Undecodable {
+        key: Vec<u8>,
+    },
+}
+
+impl<E> From<E> for MigrationError<E> {
+    fn from(e: E) -> Self {
+        MigrationError::Store(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for MigrationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Store(e) => e.fmt(f),
+            MigrationError::UnknownVersion { stored, known } => write!(
+                f,
+                "stored schema version {stored} is newer than the {known} known migrations"
+            ),
+            MigrationError::Undecodable { key } => {
+                write!(f, "value of key {key:x?} can not be decoded")
+            }
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for MigrationError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MigrationError::Store(e) => Some(e),
+            MigrationError::UnknownVersion { .. } | MigrationError::Undecodable { .. } => None,
+        }
+    }
+}
+
+/// Ordered migration steps of a single set of tables.
+///
+/// The schema version of the set is the number of completed steps. It is stored in the
+/// [`META_TABLE`] under the name of the set, so a store can hold multiple independent sets.
+pub struct Migrations<S: Store> {
+    name: String,
+    steps: Vec<Step<S>>,
+}
+
+impl<S: Store> Migrations<S> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            steps: vec![],
+        }
+    }
+
+    /// Adds a step that runs within a single write transaction.
+    pub fn step<F>(self, fun: F) -> Self
+    where
+        F: Fn(&Arc<S>, &mut WtxOf<S>) -> Result<(), S::Error> + Send + Sync + 'static,
+    {
+        self.paged_step(move |store, wtx, _| fun(store, wtx))
+    }
+
+    /// Adds a resumable step, that runs in a separate write transaction for every page.
+    ///
+    /// Every call should process a bounded amount of data and move the checkpoint past it. The
+    /// checkpoint is committed along with the changes, so a step interrupted by a crash resumes
+    /// from the last committed page. The step is done once a call leaves the checkpoint unchanged.
+    pub fn paged_step<F>(mut self, fun: F) -> Self
+    where
+        F: Fn(&Arc<S>, &mut WtxOf<S>, &mut Checkpoint) -> Result<(), S::Error>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.steps.push(Box::new(fun));
+        self
+    }
+
+    fn key(&self, what: &str) -> String {
+        format!("migrate/{}/{what}", self.name)
+    }
+
+    /// Schema version currently stored for this set of tables.
+    pub fn version(&self, store: &Arc<S>) -> Result<u64, S::Error> {
+        let versions = store.typed::<Str, OwnedType<u64>>(META_TABLE, &TableConfig::default())?;
+
+        Ok(store
+            .with_rtx(|rtx| versions.get(rtx, &self.key("version")))?
+            .unwrap_or(0))
+    }
+
+    /// Runs all pending steps, and returns the resulting schema version.
+    pub fn run(&self, store: &Arc<S>) -> Result<u64, MigrationError<S::Error>> {
+        let cfg = TableConfig::default();
+        let versions = store.typed::<Str, OwnedType<u64>>(META_TABLE, &cfg)?;
+        let checkpoints = store.typed::<Str, ByteSlice>(META_TABLE, &cfg)?;
+
+        let version_key = self.key("version");
+        let checkpoint_key = self.key("checkpoint");
+
+        let stored = self.version(store)?;
+        let known = self.steps.len() as u64;
+        if stored > known {
+            return Err(MigrationError::UnknownVersion { stored, known });
+        }
+
+        for (version, step) in (stored..).zip(&self.steps[stored as usize..]) {
+            let start = store.with_rtx(|rtx| checkpoints.get(rtx, &checkpoint_key))?;
+
+            paged(start, |checkpoint| {
+                store.with_wtx(|wtx| {
+                    let old = checkpoint.clone();
+                    step(store, wtx, checkpoint)?;
+
+                    if *checkpoint == old {
+                        checkpoints.delete(wtx, &checkpoint_key)?;
+                        versions.put(wtx, &version_key, &(version + 1))
+                    } else if let Some(c) = checkpoint {
+                        checkpoints.put(wtx, &checkpoint_key, c)
+                    } else {
+                        checkpoints.delete(wtx, &checkpoint_key)
+                    }
+                })
+            })?;
+        }
+
+        Ok(known)
+    }
+
+    /// Runs all pending steps, and opens the tables afterwards.
+    pub fn open<T, F>(
+        &self,
+        store: S,
+        cfg: &TableConfig,
+        make: F,
+    ) -> Result<Tables<S, T>, MigrationError<S::Error>>
+    where
+        F: FnOnce(&Arc<S>, &TableConfig) -> Result<T, S::Error>,
+    {
+        let store = Arc::new(store);
+        self.run(&store)?;
+        let table = make(&store, cfg)?;
+
+        Ok(Tables { store, table })
+    }
+}
+
+/// Rewrites every value of `table` from format `DC1` into `DC2` in place, passing each value
+/// through `convert`.
+///
+/// The table is processed in separate write transactions of at most `batch` entries, and
+/// `progress` is called with the number of processed entries after each of them commits. The
+/// position of the conversion is committed along with every batch in the [`META_TABLE`] under
+/// `name`, so an interrupted conversion resumes after the last committed batch, and a finished one
+/// is not repeated.
+///
+/// The table can be used while it is being converted, as long as it is written in `DC2`, and read
+/// in either format, for example as [`Fallback<DC2, DC1>`](crate::types::Fallback). Values are
+/// locked while they are converted, and values that already decode as `DC2` are left as they
+/// are, so `DC2` must reject values encoded with `DC1`. A value that decodes in neither format
+/// stops the conversion with [`MigrationError::Undecodable`], without committing its batch.
+pub fn convert_values<DC2, S, KC, DC1, V, F>(
+    store: &Arc<S>,
+    name: &str,
+    table: Typed<S, KC, DC1>,
+    batch: usize,
+    mut convert: F,
+    mut progress: impl FnMut(usize),
+) -> Result<Typed<S, KC, DC2>, MigrationError<S::Error>>
+where
+    S: Store,
+    DC1: DFormat,
+    DC2: for<'a> EFormat<'a, EItem = V> + DFormat,
+    F: FnMut(DC1::DItem) -> V,
+{
+    let cfg = TableConfig::default();
+    let counts = store.typed::<Str, OwnedType<u64>>(META_TABLE, &cfg)?;
+    let checkpoints = store.typed::<Str, ByteSlice>(META_TABLE, &cfg)?;
+
+    let done_key = format!("convert/{name}/done");
+    let count_key = format!("convert/{name}/count");
+    let checkpoint_key = format!("convert/{name}/checkpoint");
+
+    let raw = table.remap_types::<ByteSlice, ByteSlice>();
+    let (finished, start, mut done) = store.with_rtx(|rtx| {
+        Ok((
+            counts.get(rtx, &done_key)?.is_some(),
+            checkpoints.get(rtx, &checkpoint_key)?,
+            counts.get(rtx, &count_key)?.unwrap_or(0) as usize,
+        ))
+    })?;
+    if finished {
+        return Ok(raw.remap_types());
+    }
+
+    let mut undecodable = None;
+    let converted = paged(start, |cursor: &mut Option<Vec<u8>>| {
+        let mut wtx = store.wtx()?;
+        let start = match cursor {
+            Some(key) => Bound::Excluded(&key[..]),
+            None => Bound::Unbounded,
+        };
+        let keys = raw
+            .range(&wtx, &(start, Bound::Unbounded))?
+            .take(batch.max(1))
+            .map(|item| item.map(|(key, _)| key))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for key in &keys {
+            // Locks the key, and reads the latest value, which a concurrent writer may have
+            // replaced in the new format since the range was read.
+            let Some(value) = raw.get_for_update(&mut wtx, key)? else {
+                continue;
+            };
+            if DC2::decode(&value).is_some() {
+                continue;
+            }
+            match DC1::decode(&value) {
+                Some(value) => raw
+                    .table
+                    .put::<ByteSlice, DC2>(&mut wtx, key, &convert(value))?,
+                None => {
+                    // Dropping the transaction discards the batch.
+                    undecodable = Some(key.clone());
+                    return Ok(());
+                }
+            }
+        }
+
+        match keys.last() {
+            Some(last) => {
+                checkpoints.put(&mut wtx, &checkpoint_key, last)?;
+                counts.put(&mut wtx, &count_key, &((done + keys.len()) as u64))?;
+                *cursor = Some(last.clone());
+            }
+            None => {
+                checkpoints.delete(&mut wtx, &checkpoint_key)?;
+                counts.delete(&mut wtx, &count_key)?;
+                counts.put(&mut wtx, &done_key, &(done as u64))?;
+            }
+        }
+        wtx.commit()?;
+
+        if !keys.is_empty() {
+            done += keys.len();
+            progress(done);
+        }
+        Ok(())
+    });
+
+    match (converted, undecodable) {
+        (Err(e), _) => Err(MigrationError::Store(e)),
+        (Ok(()), Some(key)) => Err(MigrationError::Undecodable { key }),
+        (Ok(()), None) => Ok(raw.remap_types()),
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::Bound;
+use std::marker;
+
+use crate::format::{DFormat, EFormat};
+use crate::index::{after, escape};
+use crate::types::{ByteSlice, Empty, Ignore};
+use crate::{ErrorOf, RangeOf, RtxOf, Store, Table, WtxOf};
+
+/// Table storing any number of distinct values under every key.
+///
+/// Every value is stored as a separate entry, keyed by the escaped key followed by the encoded
+/// value, so the values of a key are ordered by their encoding.
+pub struct MultiTyped<S: Store, KC, DC> {
+    pub(crate) table: S::Table,
+    pub(crate) marker: marker::PhantomData<(KC, DC)>,
+}
+
+impl<S: Store, KC, DC> Clone for MultiTyped<S, KC, DC>
+where
+    S::Table: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            marker: Default::default(),
+        }
+    }
+}
+
+/// Encoded keys of entries, with their values ignored.
+type Entries<'txn, S> = RangeOf<'txn, S, ByteSlice, Ignore>;
+
+fn entry(prefix: &[u8], value: &[u8]) -> Vec<u8> {
+    [prefix, value].concat()
+}
+
+impl<S: Store, KC, DC> MultiTyped<S, KC, DC> {
+    /// Range over all entries of `key`, along with the length of their common prefix.
+    fn scan<'a, 'txn>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<(usize, Entries<'txn, S>), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        let prefix = escape(&KC::encode(key));
+        let end = after(prefix.clone());
+        let bounds = (Bound::Included(&prefix[..]), Bound::Excluded(&end[..]));
+
+        Ok((
+            prefix.len(),
+            self.table.range::<ByteSlice, Ignore, _>(txn, &bounds)?,
+        ))
+    }
+
+    /// Encoded keys of all entries of `key`, along with the length of their common prefix.
+    fn entries<'a>(
+        &self,
+        txn: &RtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<(usize, Vec<Vec<u8>>), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        let (prefix, range) = self.scan(txn, key)?;
+        let entries = range
+            .map(|item| item.map(|(k, _)| k))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((prefix, entries))
+    }
+
+    /// Adds `value` to the values of `key`. Adding a value that is already present has no effect.
+    pub fn insert<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+        value: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let entry = entry(&escape(&KC::encode(key)), &DC::encode(value));
+        self.table.put::<ByteSlice, Empty>(txn, &entry, &())
+    }
+
+    pub fn remove<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+        value: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let entry = entry(&escape(&KC::encode(key)), &DC::encode(value));
+        self.table.delete::<ByteSlice>(txn, &entry)
+    }
+
+    pub fn contains<'a>(
+        &self,
+        txn: &RtxOf<S>,
+        key: &'a KC::EItem,
+        value: &'a DC::EItem,
+    ) -> Result<bool, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: EFormat<'a>,
+    {
+        let entry = entry(&escape(&KC::encode(key)), &DC::encode(value));
+        Ok(self.table.get::<ByteSlice, Ignore>(txn, &entry)?.is_some())
+    }
+
+    /// All values of `key`, ordered by their encoding.
+    pub fn get_all<'a>(
+        &self,
+        txn: &RtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Vec<DC::DItem>, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+        DC: DFormat,
+    {
+        let (prefix, entries) = self.entries(txn, key)?;
+
+        Ok(entries
+            .iter()
+            .map(|e| DC::decode(&e[prefix..]).unwrap())
+            .collect())
+    }
+
+    pub fn count<'a>(&self, txn: &RtxOf<S>, key: &'a KC::EItem) -> Result<usize, ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        let mut count = 0;
+        for item in self.scan(txn, key)?.1 {
+            item?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Removes all values of `key`.
+    pub fn delete_all<'a>(&self, txn: &mut WtxOf<S>, key: &'a KC::EItem) -> Result<(), ErrorOf<S>>
+    where
+        KC: EFormat<'a>,
+    {
+        let (_, entries) = self.entries(txn, key)?;
+        for entry in entries {
+            self.table.delete::<ByteSlice>(txn, &entry)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn clear(&self, txn: &mut WtxOf<S>) -> Result<(), ErrorOf<S>> {
+        self.table.clear(txn)
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::Bound;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::format::DFormat;
+
+/// Kind of a single write within a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Put(Vec<u8>),
+    Delete,
+    Merge(Vec<u8>),
+}
+
+/// Single write within a transaction, with the key and value in their stored encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Write {
+    pub table: Arc<str>,
+    pub key: Vec<u8>,
+    pub op: Op,
+}
+
+struct Subscriber {
+    id: u64,
+    table: String,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    sender: Sender<Write>,
+}
+
+impl Subscriber {
+    fn matches(&self, write: &Write) -> bool {
+        let range = (
+            self.start.as_ref().map(Vec::as_slice),
+            self.end.as_ref().map(Vec::as_slice),
+        );
+        *write.table == self.table && range.contains(&write.key[..])
+    }
+}
+
+/// Subscribers of a store, shared with their [`Subscription`]s, so that they can unsubscribe.
+#[derive(Default)]
+struct Subscribers {
+    active: AtomicBool,
+    next: AtomicU64,
+    list: Mutex<Vec<Subscriber>>,
+}
+
+impl Subscribers {
+    /// Keeps the subscribers for which `keep` returns `true`.
+    fn retain(&self, keep: impl FnMut(&Subscriber) -> bool) {
+        let mut list = self.list.lock().unwrap();
+        list.retain(keep);
+        self.active.store(!list.is_empty(), Ordering::Release);
+    }
+}
+
+/// Reason for rejecting a commit, returned by pre-commit hooks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Veto(pub String);
+
+impl fmt::Display for Veto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "commit vetoed: {}", self.0)
+    }
+}
+
+impl std::error::Error for Veto {}
+
+pub type PreCommitHook = dyn Fn(&[Write]) -> Result<(), Veto> + Send + Sync;
+pub type PostCommitHook = dyn Fn(&[Write]) + Send + Sync;
+
+/// Identifies a registered hook, to remove it with [`Observers::remove_hook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+struct Hook<F: ?Sized> {
+    id: HookId,
+    tables: Vec<String>,
+    fun: Arc<F>,
+}
+
+impl<F: ?Sized> Hook<F> {
+    /// Writes of a transaction to the tables the hook was registered for.
+    fn writes(&self, writes: &[Write]) -> Vec<Write> {
+        writes
+            .iter()
+            .filter(|w| self.tables.is_empty() || self.tables.iter().any(|t| **t == *w.table))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Observers of the transactions committed to a store.
+///
+/// Write transactions record their writes only while there are observers. Before committing they
+/// pass them to [`Observers::committing`], which can veto the commit, and once committed to
+/// [`Observers::committed`]. Backends hold a single instance, and return it from
+/// [`Store::observers`](crate::Store::observers).
+#[derive(Default)]
+pub struct Observers {
+    subscribers: Arc<Subscribers>,
+    hooks: AtomicUsize,
+    next_hook: AtomicU64,
+    pre_commit: Mutex<Vec<Hook<PreCommitHook>>>,
+    post_commit: Mutex<Vec<Hook<PostCommitHook>>>,
+}
+
+impl Observers {
+    /// Whether write transactions have to record their writes.
+    pub fn active(&self) -> bool {
+        self.subscribers.active.load(Ordering::Acquire) || self.hooks.load(Ordering::Acquire) > 0
+    }
+
+    fn hook<F: ?Sized>(&self, tables: &[&str], fun: Arc<F>) -> Hook<F> {
+        self.hooks.fetch_add(1, Ordering::AcqRel);
+        Hook {
+            id: HookId(self.next_hook.fetch_add(1, Ordering::Relaxed)),
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            fun,
+        }
+    }
+
+    /// Registers a hook called with the writes of every transaction to `tables`, or to any table
+    /// if `tables` is empty, before it commits. Returning an error aborts the commit, which then
+    /// fails with the backend's veto error.
+    ///
+    /// The hook runs while the transaction is still open, and must not start write transactions
+    /// itself. Transactions that were already writing when it was registered might pass it only
+    /// some of their writes.
+    pub fn on_pre_commit(
+        &self,
+        tables: &[&str],
+        hook: impl Fn(&[Write]) -> Result<(), Veto> + Send + Sync + 'static,
+    ) -> HookId {
+        let hook = self.hook::<PreCommitHook>(tables, Arc::new(hook));
+        let id = hook.id;
+        self.pre_commit.lock().unwrap().push(hook);
+        id
+    }
+
+    /// Registers a hook called with the writes of every transaction to `tables`, or to any table
+    /// if `tables` is empty, after it successfully commits.
+    pub fn on_post_commit(
+        &self,
+        tables: &[&str],
+        hook: impl Fn(&[Write]) + Send + Sync + 'static,
+    ) -> HookId {
+        let hook = self.hook::<PostCommitHook>(tables, Arc::new(hook));
+        let id = hook.id;
+        self.post_commit.lock().unwrap().push(hook);
+        id
+    }
+
+    /// Removes a hook. Returns `false` if it was already removed.
+    pub fn remove_hook(&self, id: HookId) -> bool {
+        let mut pre = self.pre_commit.lock().unwrap();
+        let mut post = self.post_commit.lock().unwrap();
+        let before = pre.len() + post.len();
+        pre.retain(|h| h.id != id);
+        post.retain(|h| h.id != id);
+
+        let removed = before - pre.len() - post.len();
+        self.hooks.fetch_sub(removed, Ordering::AcqRel);
+        removed > 0
+    }
+
+    /// Appends a write to the write set of a transaction, if there are any observers.
+    pub fn record(
+        &self,
+        writes: &mut Vec<Write>,
+        table: &Arc<str>,
+        key: &[u8],
+        op: impl FnOnce() -> Op,
+    ) {
+        if self.active() {
+            writes.push(Write {
+                table: table.clone(),
+                key: key.to_vec(),
+                op: op(),
+            });
+        }
+    }
+
+    /// Passes the writes of a transaction that is about to commit to the pre-commit hooks.
+    /// Returns the first veto, in which case the transaction must not be committed.
+    pub fn committing(&self, writes: &[Write]) -> Result<(), Veto> {
+        if writes.is_empty() {
+            return Ok(());
+        }
+
+        // Called without holding the lock, so that hooks can manage other hooks.
+        let hooks = self
+            .pre_commit
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|h| (h.writes(writes), h.fun.clone()))
+            .collect::<Vec<_>>();
+
+        for (writes, hook) in hooks {
+            if !writes.is_empty() {
+                hook(&writes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Notifies observers about the writes of a transaction, that was successfully committed.
+    pub fn committed(&self, writes: &[Write]) {
+        if writes.is_empty() {
+            return;
+        }
+
+        let hooks = self
+            .post_commit
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|h| (h.writes(writes), h.fun.clone()))
+            .collect::<Vec<_>>();
+
+        for (writes, hook) in hooks {
+            if !writes.is_empty() {
+                hook(&writes);
+            }
+        }
+
+        // Subscriptions dropped while they are notified fail to receive, and are removed here.
+        self.subscribers.retain(|sub| {
+            writes
+                .iter()
+                .filter(|w| sub.matches(w))
+                .all(|w| sub.sender.send(w.clone()).is_ok())
+        });
+    }
+
+    pub(crate) fn subscribe<KC, DC>(
+        &self,
+        table: &str,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+    ) -> Subscription<KC, DC> {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.subscribers.next.fetch_add(1, Ordering::Relaxed);
+
+        let mut subscribers = self.subscribers.list.lock().unwrap();
+        subscribers.push(Subscriber {
+            id,
+            table: table.to_string(),
+            start,
+            end,
+            sender,
+        });
+        self.subscribers.active.store(true, Ordering::Release);
+
+        Subscription {
+            id,
+            subscribers: Arc::downgrade(&self.subscribers),
+            receiver,
+            _p: PhantomData,
+        }
+    }
+}
+
+/// Exclusive upper bound of keys starting with `prefix`.
+pub(crate) fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xFF {
+            end.push(last + 1);
+            return Bound::Excluded(end);
+        }
+    }
+    Bound::Unbounded
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<K, V> {
+    Put {
+        key: K,
+        value: V,
+    },
+    Delete {
+        key: K,
+    },
+    Merge {
+        key: K,
+        operand: V,
+    },
+    /// Change whose key or value does not decode in the formats of the subscription, such as a
+    /// value of an [`Expiring`](crate::expiring::Expiring) table. The key is in its stored
+    /// encoding.
+    Malformed {
+        key: Vec<u8>,
+    },
+}
+
+/// Stream of committed changes to a range of keys, created by
+/// [`Store::subscribe`](crate::Store::subscribe).
+///
+/// Changes are delivered once their commit succeeds, changes of transactions committing
+/// concurrently may arrive in any order. The subscription ends when the store is dropped, and
+/// unsubscribes when dropped itself.
+pub struct Subscription<KC, DC> {
+    id: u64,
+    subscribers: Weak<Subscribers>,
+    receiver: Receiver<Write>,
+    _p: PhantomData<fn() -> (KC, DC)>,
+}
+
+impl<KC, DC> Drop for Subscription<KC, DC> {
+    fn drop(&mut self) {
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            subscribers.retain(|sub| sub.id != self.id);
+        }
+    }
+}
+
+impl<KC: DFormat, DC: DFormat> Subscription<KC, DC> {
+    fn decode(write: Write) -> Event<KC::DItem, DC::DItem> {
+        let event = KC::decode(&write.key).and_then(|key| match &write.op {
+            Op::Put(value) => DC::decode(value).map(|value| Event::Put { key, value }),
+            Op::Delete => Some(Event::Delete { key }),
+            Op::Merge(operand) => DC::decode(operand).map(|operand| Event::Merge { key, operand }),
+        });
+
+        event.unwrap_or(Event::Malformed { key: write.key })
+    }
+
+    /// Waits for the next change. Returns `None` once the store is dropped.
+    pub fn recv(&self) -> Option<Event<KC::DItem, DC::DItem>> {
+        self.receiver.recv().ok().map(Self::decode)
+    }
+
+    /// Next change, if there is one already.
+    pub fn try_recv(&self) -> Option<Event<KC::DItem, DC::DItem>> {
+        self.receiver.try_recv().ok().map(Self::decode)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event<KC::DItem, DC::DItem>> {
+        self.receiver.recv_timeout(timeout).ok().map(Self::decode)
+    }
+}
+
+impl<KC: DFormat, DC: DFormat> Iterator for Subscription<KC, DC> {
+    type Item = Event<KC::DItem, DC::DItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
@@ -0,0 +1,67 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::config::TableConfig;
+use crate::types::{OwnedType, Str};
+use crate::{Store, Typed, WtxOf, META_TABLE};
+
+/// Persistent sequence of monotonically increasing IDs, starting at 1.
+///
+/// The last ID handed out is stored in the [`META_TABLE`] under the name of the sequence.
+pub struct Sequence<S: Store> {
+    store: Arc<S>,
+    key: String,
+    ids: Typed<S, Str, OwnedType<u64>>,
+    block_size: u64,
+    /// IDs reserved by [`Sequence::allocate`], that were not handed out yet.
+    block: Mutex<Range<u64>>,
+}
+
+impl<S: Store> Sequence<S> {
+    pub fn new(store: &Arc<S>, name: &str) -> Result<Self, S::Error> {
+        Ok(Self {
+            store: store.clone(),
+            key: format!("sequence/{name}"),
+            ids: store.typed(META_TABLE, &TableConfig::default())?,
+            block_size: 1,
+            block: Mutex::new(0..0),
+        })
+    }
+
+    /// Number of IDs reserved at once by [`Sequence::allocate`].
+    pub fn block_size(mut self, size: u64) -> Self {
+        self.block_size = size.max(1);
+        self
+    }
+
+    fn reserve(&self, txn: &mut WtxOf<S>, count: u64) -> Result<u64, S::Error> {
+        let last = self.ids.get_for_update(txn, &self.key)?.unwrap_or(0);
+        self.ids.put(txn, &self.key, &(last + count))?;
+
+        Ok(last + 1)
+    }
+
+    /// Hands out the next ID within `txn`. The sequence stays locked until the transaction ends,
+    /// and the ID is returned to the sequence if it does not commit, so IDs are gapless.
+    pub fn next(&self, txn: &mut WtxOf<S>) -> Result<u64, S::Error> {
+        self.reserve(txn, 1)
+    }
+
+    /// Hands out the next ID independently of any transaction.
+    ///
+    /// IDs are taken from blocks reserved in separate write transactions, so concurrent writers
+    /// only contend for the sequence once per block. IDs left in the block when the sequence is
+    /// dropped are lost, and IDs allocated concurrently by multiple processes interleave. Must not
+    /// be called while holding a write transaction of a store that serializes them.
+    pub fn allocate(&self) -> Result<u64, S::Error> {
+        let mut block = self.block.lock().unwrap();
+        if block.is_empty() {
+            let start = self
+                .store
+                .with_wtx(|wtx| self.reserve(wtx, self.block_size))?;
+            *block = start..start + self.block_size;
+        }
+
+        Ok(block.next().unwrap())
+    }
+}
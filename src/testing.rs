@@ -0,0 +1,573 @@
+//! Conformance checks for [`Store`] backends.
+//!
+//! [`conformance`] runs every check against fresh stores created by a factory, and panics on the
+//! first behavior that differs from the in-memory reference backend. Third-party backends can run
+//! it from their own tests:
+//!
+//! ```ignore
+//! #[test]
+//! fn conformance() {
+//!     stor::testing::conformance(|| Arc::new(MyStore::new()));
+//! }
+//! ```
+//!
+//! [`model`] additionally compares a store against a [`BTreeMap`] model, on random sequences of
+//! operations generated by proptest.
+
+use std::collections::{BTreeMap, Bound};
+use std::fmt::Display;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use proptest::prelude::*;
+use proptest::test_runner::{TestCaseError, TestRunner};
+
+use crate::config::TableConfig;
+use crate::merge::{Counter, MergeOperator};
+use crate::types::ByteSlice;
+use crate::{Store, Transaction, Typed};
+
+/// Keys chosen to exercise byte-wise ordering, including keys that are prefixes of each other.
+const KEYS: &[&[u8]] = &[
+    b"",
+    b"a",
+    b"a\x00",
+    b"a\x00\x00",
+    b"a\x01",
+    b"a\xff",
+    b"a\xff\xff",
+    b"ab",
+    b"b",
+    b"\xff",
+];
+
+/// Probes used as range bounds, both present and missing keys.
+const PROBES: &[&[u8]] = &[
+    b"",
+    b"\x00",
+    b"a",
+    b"a\x00",
+    b"a\x01",
+    b"a\x7f",
+    b"a\xff",
+    b"aa",
+    b"b",
+    b"c",
+    b"\xff",
+    b"\xff\xff",
+];
+
+/// Runs all checks, each against a new store created by `store`.
+pub fn conformance<S: Store>(store: impl Fn() -> Arc<S>) {
+    get_put_delete(&store());
+    len(&store());
+    commit_visibility(&store());
+    rollback_on_drop(&store());
+    table_isolation(&store());
+    clear(&store());
+    merge(&store());
+    drop_table(&store());
+    rename_table(&store());
+    range_bounds(&store());
+}
+
+fn table<S: Store>(store: &Arc<S>, name: &str) -> Typed<S, ByteSlice, ByteSlice> {
+    store.typed(name, &TableConfig::default()).unwrap()
+}
+
+fn fill<S: Store>(store: &Arc<S>, table: &Typed<S, ByteSlice, ByteSlice>, keys: &[&[u8]]) {
+    store
+        .with_wtx(|wtx| {
+            for key in keys {
+                table.put(wtx, key, &[key, &b"-value"[..]].concat())?;
+            }
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Point reads and writes, within a transaction and after it commits.
+pub fn get_put_delete<S: Store>(store: &Arc<S>) {
+    let t = table(store, "conformance");
+
+    let mut wtx = store.wtx().unwrap();
+    assert_eq!(t.get(&wtx, b"k").unwrap(), None, "missing key");
+    t.put(&mut wtx, b"k", b"1").unwrap();
+    assert_eq!(t.get(&wtx, b"k").unwrap(), Some(b"1".to_vec()), "own write");
+    t.put(&mut wtx, b"k", b"2").unwrap();
+    assert_eq!(t.get(&wtx, b"k").unwrap(), Some(b"2".to_vec()), "overwrite");
+    t.append(&mut wtx, b"l", b"3").unwrap();
+    assert_eq!(t.get(&wtx, b"l").unwrap(), Some(b"3".to_vec()), "append");
+    assert_eq!(
+        t.get_for_update(&mut wtx, b"l").unwrap(),
+        Some(b"3".to_vec())
+    );
+    t.put(&mut wtx, b"", b"").unwrap();
+    assert_eq!(
+        t.get(&wtx, b"").unwrap(),
+        Some(vec![]),
+        "empty key and value"
+    );
+    wtx.commit().unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(t.get(&rtx, b"k").unwrap(), Some(b"2".to_vec()), "committed");
+    assert_eq!(t.get(&rtx, b"l").unwrap(), Some(b"3".to_vec()), "committed");
+    drop(rtx);
+
+    let mut wtx = store.wtx().unwrap();
+    t.delete(&mut wtx, b"k").unwrap();
+    t.delete(&mut wtx, b"missing").unwrap();
+    assert_eq!(t.get(&wtx, b"k").unwrap(), None, "own delete");
+    wtx.commit().unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(t.get(&rtx, b"k").unwrap(), None, "committed delete");
+    assert_eq!(t.get(&rtx, b"missing").unwrap(), None);
+}
+
+/// Number of entries, including uncommitted writes of the transaction.
+pub fn len<S: Store>(store: &Arc<S>) {
+    let t = table(store, "conformance");
+    assert_eq!(store.with_rtx(|rtx| t.len(rtx)).unwrap(), 0, "new table");
+
+    fill(store, &t, KEYS);
+    assert_eq!(store.with_rtx(|rtx| t.len(rtx)).unwrap(), KEYS.len());
+
+    let mut wtx = store.wtx().unwrap();
+    t.put(&mut wtx, b"a", b"overwritten").unwrap();
+    assert_eq!(t.len(&wtx).unwrap(), KEYS.len(), "overwrite");
+    t.put(&mut wtx, b"new", b"").unwrap();
+    assert_eq!(t.len(&wtx).unwrap(), KEYS.len() + 1, "own insert");
+    t.delete(&mut wtx, b"b").unwrap();
+    t.delete(&mut wtx, b"b").unwrap();
+    assert_eq!(t.len(&wtx).unwrap(), KEYS.len(), "own delete");
+    wtx.commit().unwrap();
+
+    assert_eq!(store.with_rtx(|rtx| t.len(rtx)).unwrap(), KEYS.len());
+}
+
+/// Writes become visible to other transactions only once committed.
+pub fn commit_visibility<S: Store>(store: &Arc<S>) {
+    let t = table(store, "conformance");
+    fill(store, &t, &[b"old"]);
+
+    let mut wtx = store.wtx().unwrap();
+    t.put(&mut wtx, b"new", b"").unwrap();
+    t.delete(&mut wtx, b"old").unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(t.get(&rtx, b"new").unwrap(), None, "uncommitted insert");
+    assert!(t.get(&rtx, b"old").unwrap().is_some(), "uncommitted delete");
+    assert_eq!(t.len(&rtx).unwrap(), 1, "uncommitted len");
+    drop(rtx);
+
+    wtx.commit().unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert!(t.get(&rtx, b"new").unwrap().is_some(), "committed insert");
+    assert_eq!(t.get(&rtx, b"old").unwrap(), None, "committed delete");
+}
+
+/// Dropping a write transaction discards its writes.
+pub fn rollback_on_drop<S: Store>(store: &Arc<S>) {
+    let t = table(store, "conformance");
+    fill(store, &t, &[b"kept"]);
+
+    let mut wtx = store.wtx().unwrap();
+    t.put(&mut wtx, b"dropped", b"").unwrap();
+    t.delete(&mut wtx, b"kept").unwrap();
+    t.clear(&mut wtx).unwrap();
+    drop(wtx);
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(t.get(&rtx, b"dropped").unwrap(), None);
+    assert!(t.get(&rtx, b"kept").unwrap().is_some());
+    assert_eq!(t.len(&rtx).unwrap(), 1);
+}
+
+/// Tables with the same keys do not see each other's entries.
+pub fn table_isolation<S: Store>(store: &Arc<S>) {
+    let a = table(store, "conformance_a");
+    let b = table(store, "conformance_b");
+    fill(store, &a, KEYS);
+    fill(store, &b, &KEYS[..3]);
+
+    store
+        .with_wtx(|wtx| {
+            a.put(wtx, b"a", b"in a")?;
+            b.delete(wtx, b"")
+        })
+        .unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(a.len(&rtx).unwrap(), KEYS.len());
+    assert_eq!(b.len(&rtx).unwrap(), 2);
+    assert_eq!(b.get(&rtx, b"a").unwrap(), Some(b"a-value".to_vec()));
+    assert!(a.get(&rtx, b"").unwrap().is_some());
+    assert_eq!(b.get(&rtx, b"b").unwrap(), None);
+
+    let keys = b
+        .range(&rtx, &..)
+        .unwrap()
+        .map(|item| item.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec![b"a".to_vec(), b"a\x00".to_vec()]);
+
+    let names = store.list_tables().unwrap();
+    assert!(names.iter().any(|n| n == "conformance_a"));
+    assert!(names.iter().any(|n| n == "conformance_b"));
+}
+
+/// Clearing removes all entries of a table, and only of that table.
+pub fn clear<S: Store>(store: &Arc<S>) {
+    let a = table(store, "conformance_a");
+    let b = table(store, "conformance_b");
+    fill(store, &a, KEYS);
+    fill(store, &b, KEYS);
+
+    let mut wtx = store.wtx().unwrap();
+    a.clear(&mut wtx).unwrap();
+    assert_eq!(a.len(&wtx).unwrap(), 0, "own clear");
+    a.put(&mut wtx, b"after", b"").unwrap();
+    wtx.commit().unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(a.len(&rtx).unwrap(), 1);
+    assert_eq!(a.range(&rtx, &..).unwrap().count(), 1);
+    assert_eq!(a.rev_range(&rtx, &..).unwrap().count(), 1);
+    assert_eq!(a.get(&rtx, b"a").unwrap(), None);
+    assert_eq!(b.len(&rtx).unwrap(), KEYS.len(), "other table");
+}
+
+/// Merge operands combine with the stored value, and with each other.
+pub fn merge<S: Store>(store: &Arc<S>) {
+    let cfg = TableConfig {
+        merge: Some(MergeOperator::of::<Counter>()),
+        ..Default::default()
+    };
+    let t = store
+        .typed::<ByteSlice, Counter>("conformance", &cfg)
+        .unwrap();
+
+    let mut wtx = store.wtx().unwrap();
+    t.merge(&mut wtx, b"new", &2).unwrap();
+    t.merge(&mut wtx, b"new", &3).unwrap();
+    assert_eq!(t.get(&wtx, b"new").unwrap(), Some(5), "own merges");
+    t.put(&mut wtx, b"put", &10).unwrap();
+    t.merge(&mut wtx, b"put", &-1).unwrap();
+    assert_eq!(t.get(&wtx, b"put").unwrap(), Some(9), "merge after put");
+    wtx.commit().unwrap();
+
+    let mut wtx = store.wtx().unwrap();
+    t.merge(&mut wtx, b"new", &100).unwrap();
+    drop(wtx);
+
+    store.with_wtx(|wtx| t.merge(wtx, b"put", &1)).unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(t.get(&rtx, b"new").unwrap(), Some(5), "dropped merge");
+    assert_eq!(t.get(&rtx, b"put").unwrap(), Some(10), "committed merges");
+    let entries = t
+        .range(&rtx, &..)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries, vec![(b"new".to_vec(), 5), (b"put".to_vec(), 10)]);
+}
+
+/// Dropped tables disappear with their entries, and can be created again.
+pub fn drop_table<S: Store>(store: &Arc<S>) {
+    let a = table(store, "conformance_a");
+    let b = table(store, "conformance_b");
+    fill(store, &a, KEYS);
+    fill(store, &b, KEYS);
+
+    store.drop_table("conformance_a").unwrap();
+    let names = store.list_tables().unwrap();
+    assert!(!names.iter().any(|n| n == "conformance_a"), "dropped");
+    assert!(names.iter().any(|n| n == "conformance_b"), "other table");
+    assert!(store.drop_table("conformance_a").is_err(), "missing table");
+
+    let a = table(store, "conformance_a");
+    let rtx = store.rtx().unwrap();
+    assert_eq!(a.len(&rtx).unwrap(), 0, "recreated");
+    assert_eq!(b.len(&rtx).unwrap(), KEYS.len(), "other table");
+}
+
+/// Renamed tables keep their entries under the new name.
+pub fn rename_table<S: Store>(store: &Arc<S>) {
+    let from = table(store, "conformance_from");
+    fill(store, &from, KEYS);
+    let expected = store
+        .with_rtx(|rtx| from.range(rtx, &..)?.collect::<Result<Vec<_>, _>>())
+        .unwrap();
+    table(store, "conformance_taken");
+
+    let cfg = TableConfig::default();
+    assert!(
+        store
+            .rename_table("conformance_from", "conformance_taken", &cfg)
+            .is_err(),
+        "existing target"
+    );
+    assert!(
+        store
+            .rename_table("conformance_missing", "conformance_to", &cfg)
+            .is_err(),
+        "missing source"
+    );
+    store
+        .rename_table("conformance_from", "conformance_to", &cfg)
+        .unwrap();
+
+    let names = store.list_tables().unwrap();
+    assert!(!names.iter().any(|n| n == "conformance_from"), "renamed");
+    assert!(names.iter().any(|n| n == "conformance_to"), "renamed");
+
+    let to = table(store, "conformance_to");
+    let entries = store
+        .with_rtx(|rtx| to.range(rtx, &..)?.collect::<Result<Vec<_>, _>>())
+        .unwrap();
+    assert_eq!(entries, expected);
+}
+
+fn bounds(probe: &[u8]) -> [Bound<&[u8]>; 3] {
+    [
+        Bound::Included(probe),
+        Bound::Excluded(probe),
+        Bound::Unbounded,
+    ]
+}
+
+/// Every combination of bounds, for both iteration directions.
+pub fn range_bounds<S: Store>(store: &Arc<S>) {
+    let t = table(store, "conformance");
+    fill(store, &t, KEYS);
+    let model = KEYS
+        .iter()
+        .map(|k| (k.to_vec(), [k, &b"-value"[..]].concat()))
+        .collect::<BTreeMap<_, _>>();
+
+    let rtx = store.rtx().unwrap();
+    for start in PROBES {
+        for end in PROBES {
+            for range in bounds(start)
+                .into_iter()
+                .flat_map(|start| bounds(end).map(|end| (start, end)))
+            {
+                let expected = model
+                    .iter()
+                    .filter(|(k, _)| range.contains(&k[..]))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect::<Vec<_>>();
+
+                let forward = t
+                    .range(&rtx, &range)
+                    .unwrap()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                assert_eq!(forward, expected, "range {range:?}");
+
+                let mut reverse = t
+                    .rev_range(&rtx, &range)
+                    .unwrap()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                reverse.reverse();
+                assert_eq!(reverse, expected, "rev_range {range:?}");
+            }
+        }
+    }
+}
+
+/// Single step of a model test. Tables are identified by their index.
+#[derive(Debug, Clone)]
+pub enum Op {
+    Put {
+        table: usize,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        table: usize,
+        key: Vec<u8>,
+    },
+    Get {
+        table: usize,
+        key: Vec<u8>,
+    },
+    Range {
+        table: usize,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+        rev: bool,
+    },
+    Len {
+        table: usize,
+    },
+    Clear {
+        table: usize,
+    },
+    /// Commits the current write transaction, and starts a new one.
+    Commit,
+    /// Drops the current write transaction, and starts a new one.
+    Rollback,
+}
+
+/// Number of tables operations are spread over.
+const MODEL_TABLES: usize = 2;
+
+/// Short keys made mostly of a few bytes, so that operations often hit the same keys, and keys
+/// are often prefixes of each other.
+fn key() -> impl Strategy<Value = Vec<u8>> {
+    let byte = prop_oneof![
+        Just(0x00),
+        Just(0x01),
+        Just(b'a'),
+        Just(0xFE),
+        Just(0xFF),
+        any::<u8>()
+    ];
+    prop::collection::vec(byte, 0..4)
+}
+
+fn bound() -> impl Strategy<Value = Bound<Vec<u8>>> {
+    prop_oneof![
+        key().prop_map(Bound::Included),
+        key().prop_map(Bound::Excluded),
+        Just(Bound::Unbounded),
+    ]
+}
+
+/// Random sequences of operations.
+pub fn ops() -> impl Strategy<Value = Vec<Op>> {
+    let table = 0..MODEL_TABLES;
+    let op = prop_oneof![
+        6 => (table.clone(), key(), prop::collection::vec(any::<u8>(), 0..4))
+            .prop_map(|(table, key, value)| Op::Put { table, key, value }),
+        3 => (table.clone(), key()).prop_map(|(table, key)| Op::Delete { table, key }),
+        2 => (table.clone(), key()).prop_map(|(table, key)| Op::Get { table, key }),
+        4 => (table.clone(), bound(), bound(), any::<bool>())
+            .prop_map(|(table, start, end, rev)| Op::Range { table, start, end, rev }),
+        1 => table.clone().prop_map(|table| Op::Len { table }),
+        1 => table.prop_map(|table| Op::Clear { table }),
+        1 => Just(Op::Commit),
+        1 => Just(Op::Rollback),
+    ];
+    prop::collection::vec(op, 0..64)
+}
+
+fn fail(e: impl Display) -> TestCaseError {
+    TestCaseError::fail(e.to_string())
+}
+
+type Model = Vec<BTreeMap<Vec<u8>, Vec<u8>>>;
+
+/// Applies `ops` to a store and to the model, and compares the results of all reads. Committed
+/// contents of all tables are compared after every commit and rollback, and at the end.
+pub fn check<S: Store>(store: &Arc<S>, ops: &[Op]) -> Result<(), TestCaseError> {
+    let tables = (0..MODEL_TABLES)
+        .map(|i| {
+            store.typed::<ByteSlice, ByteSlice>(&format!("model_{i}"), &TableConfig::default())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(fail)?;
+
+    let verify = |committed: &Model| -> Result<(), TestCaseError> {
+        let rtx = store.rtx().map_err(fail)?;
+        for (i, (table, model)) in tables.iter().zip(committed).enumerate() {
+            let stored = table
+                .range(&rtx, &..)
+                .map_err(fail)?
+                .collect::<Result<BTreeMap<_, _>, _>>()
+                .map_err(fail)?;
+            prop_assert_eq!(&stored, model, "committed contents of model_{}", i);
+        }
+        Ok(())
+    };
+
+    let mut committed: Model = vec![BTreeMap::new(); MODEL_TABLES];
+    let mut model = committed.clone();
+    let mut wtx = store.wtx().map_err(fail)?;
+
+    for op in ops {
+        match op {
+            Op::Put { table, key, value } => {
+                tables[*table].put(&mut wtx, key, value).map_err(fail)?;
+                model[*table].insert(key.clone(), value.clone());
+            }
+            Op::Delete { table, key } => {
+                tables[*table].delete(&mut wtx, key).map_err(fail)?;
+                model[*table].remove(key);
+            }
+            Op::Get { table, key } => {
+                let stored = tables[*table].get(&wtx, key).map_err(fail)?;
+                prop_assert_eq!(stored.as_ref(), model[*table].get(key), "{:?}", op);
+            }
+            Op::Range {
+                table,
+                start,
+                end,
+                rev,
+            } => {
+                let range = (
+                    start.as_ref().map(Vec::as_slice),
+                    end.as_ref().map(Vec::as_slice),
+                );
+                let mut expected = model[*table]
+                    .iter()
+                    .filter(|(k, _)| range.contains(&k[..]))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect::<Vec<_>>();
+
+                let stored = if *rev {
+                    expected.reverse();
+                    tables[*table]
+                        .rev_range(&wtx, &range)
+                        .map_err(fail)?
+                        .collect::<Result<Vec<_>, _>>()
+                } else {
+                    tables[*table]
+                        .range(&wtx, &range)
+                        .map_err(fail)?
+                        .collect::<Result<Vec<_>, _>>()
+                };
+                prop_assert_eq!(stored.map_err(fail)?, expected, "{:?}", op);
+            }
+            Op::Len { table } => {
+                let len = tables[*table].len(&wtx).map_err(fail)?;
+                prop_assert_eq!(len, model[*table].len(), "{:?}", op);
+            }
+            Op::Clear { table } => {
+                tables[*table].clear(&mut wtx).map_err(fail)?;
+                model[*table].clear();
+            }
+            Op::Commit => {
+                wtx.commit().map_err(fail)?;
+                committed = model.clone();
+                verify(&committed)?;
+                wtx = store.wtx().map_err(fail)?;
+            }
+            Op::Rollback => {
+                drop(wtx);
+                model = committed.clone();
+                verify(&committed)?;
+                wtx = store.wtx().map_err(fail)?;
+            }
+        }
+    }
+
+    wtx.commit().map_err(fail)?;
+    verify(&model)
+}
+
+/// Runs [`check`] on random sequences of operations, each against a new store created by
+/// `store`. Panics with the smallest failing sequence found. The number of cases can be set
+/// through the `PROPTEST_CASES` environment variable.
+pub fn model<S: Store>(store: impl Fn() -> Arc<S>) {
+    let mut runner = TestRunner::default();
+    if let Err(e) = runner.run(&ops(), |ops| check(&store(), &ops)) {
+        panic!("{e}");
+    }
+}
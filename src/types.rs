@@ -230,6 +230,63 @@ impl<E, D> DFormat for Split<E, D>
     }
 }
 
+/// Encodes using `A`, and decodes using `A`, falling back to `B` for values `A` rejects.
+/// Useful while values of a table are being converted between formats.
+pub struct Fallback<A, B>(std::marker::PhantomData<(A, B)>);
+
+impl<'e, A, B> EFormat<'e> for Fallback<A, B>
+    where A: EFormat<'e> + 'static,
+          B: 'static {
+    type EItem = A::EItem;
+
+    fn encode(value: &'e Self::EItem) -> Cow<'e, [u8]> {
+        A::encode(value)
+    }
+}
+
+impl<A, B> DFormat for Fallback<A, B>
+    where A: DFormat + 'static,
+          B: DFormat<DItem = A::DItem> + 'static {
+    type DItem = A::DItem;
+
+    fn decode(data: &[u8]) -> Option<Self::DItem> {
+        A::decode(data).or_else(|| B::decode(data))
+    }
+}
+
+/// Describes an integer stored in big-endian byte order, so that keys sort in numeric order.
+/// Signed integers have their sign bit flipped, so negative numbers sort before positive ones.
+///
+/// Unlike [`OwnedType`], which stores integers in native byte order, this is the format to use
+/// for integer keys of tables that are read with ranges.
+pub struct BigEndian<T>(std::marker::PhantomData<T>);
+
+macro_rules! big_endian {
+    ($($int:ty => $bits:ty),*) => {$(
+        impl EFormat<'_> for BigEndian<$int> {
+            type EItem = $int;
+
+            fn encode(item: &Self::EItem) -> Cow<'_, [u8]> {
+                // `MIN` is zero for unsigned integers, and just the sign bit for signed ones
+                let bits = (*item as $bits) ^ (<$int>::MIN as $bits);
+                Cow::Owned(bits.to_be_bytes().to_vec())
+            }
+        }
+
+        impl DFormat for BigEndian<$int> {
+            type DItem = $int;
+
+            fn decode(bytes: &[u8]) -> Option<Self::DItem> {
+                let bits = <$bits>::from_be_bytes(bytes.try_into().ok()?);
+                Some((bits ^ (<$int>::MIN as $bits)) as $int)
+            }
+        }
+    )*};
+}
+
+big_endian!(u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128);
+big_endian!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
 #[cfg(feature = "format-protokit")]
 pub struct Protokit<T>(std::marker::PhantomData<T>);
 
@@ -0,0 +1,134 @@
+//! Transactions and range streams of [`stor::async_store::AsyncStore`].
+#![cfg(all(feature = "async", feature = "impl-mem"))]
+
+use std::future::poll_fn;
+use std::ops::Bound;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+use stor::async_store::AsyncStore;
+use stor::config::TableConfig;
+use stor::db::mem::{MemError, MemStore};
+use stor::types::{OwnedType, Str};
+use stor::{Store, Typed};
+
+type Users = Typed<MemStore, Str, OwnedType<u64>>;
+
+async fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+    poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+}
+
+async fn collect<S, K, V, E>(mut stream: S) -> Vec<K>
+where
+    S: Stream<Item = Result<(K, V), E>> + Unpin,
+    E: std::fmt::Debug,
+{
+    let mut keys = vec![];
+    while let Some(item) = next(&mut stream).await {
+        keys.push(item.unwrap().0);
+    }
+    keys
+}
+
+/// Store with `user00` to `user09`, mapped to their numbers.
+async fn filled() -> (AsyncStore<MemStore>, Users) {
+    let store = AsyncStore::new(Arc::new(MemStore::new()));
+    let users: Users = store
+        .store()
+        .typed("users", &TableConfig::default())
+        .unwrap();
+
+    let writer = users.clone();
+    store
+        .with_wtx(move |wtx| {
+            for i in 0..10u64 {
+                writer.put(wtx, &format!("user{i:02}"), &i)?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    (store, users)
+}
+
+fn names(range: std::ops::Range<u64>) -> Vec<String> {
+    range.map(|i| format!("user{i:02}")).collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn write_then_read() {
+    let (store, users) = filled().await;
+
+    let reader = users.clone();
+    let (alice, count) = store
+        .with_rtx(move |rtx| Ok((reader.get(rtx, "user03")?, reader.len(rtx)?)))
+        .await
+        .unwrap();
+    assert_eq!(alice, Some(3));
+    assert_eq!(count, 10);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn closure_errors_are_returned() {
+    let (store, users) = filled().await;
+
+    let writer = users.clone();
+    let failed = store
+        .with_wtx(move |wtx| {
+            writer.put(wtx, "user10", &10)?;
+            Err::<(), _>(MemError::TableNotFound("missing".to_string()))
+        })
+        .await;
+    assert!(matches!(failed, Err(MemError::TableNotFound(name)) if name == "missing"));
+
+    // The failed transaction was not committed.
+    let reader = users.clone();
+    let missing = store
+        .with_rtx(move |rtx| reader.get(rtx, "user10"))
+        .await
+        .unwrap();
+    assert_eq!(missing, None);
+
+    let failed = store
+        .with_rtx(|_| Err::<(), _>(MemError::NestedWrite))
+        .await;
+    assert!(matches!(failed, Err(MemError::NestedWrite)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_span_batches() {
+    let (store, users) = filled().await;
+
+    assert_eq!(collect(store.range(&users, &.., 3)).await, names(0..10));
+
+    let mut reversed = collect(store.rev_range(&users, &.., 3)).await;
+    reversed.reverse();
+    assert_eq!(reversed, names(0..10));
+
+    let range = (Bound::Included("user02"), Bound::Excluded("user09"));
+    assert_eq!(collect(store.range(&users, &range, 2)).await, names(2..9));
+    let past = (Bound::Included("user20"), Bound::Unbounded);
+    assert_eq!(collect(store.range(&users, &past, 2)).await, names(0..0));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dropped_streams_stop_reading() {
+    let (store, users) = filled().await;
+    let handles = Arc::strong_count(store.store());
+
+    // The reading thread fills the channel, and waits for the stream to take a batch.
+    let mut stream = store.range(&users, &.., 1);
+    let first = next(&mut stream).await.unwrap().unwrap();
+    assert_eq!(first, ("user00".to_string(), 0));
+    drop(stream);
+
+    // It holds handles to the store until it returns.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Arc::strong_count(store.store()) > handles {
+        assert!(Instant::now() < deadline, "reading thread did not stop");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
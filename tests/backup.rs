@@ -0,0 +1,89 @@
+//! Checkpoints and backups of RocksDB stores.
+#![cfg(feature = "impl-rocks")]
+mod common;
+
+use std::sync::Arc;
+
+use common::{rocks_options, TempDir};
+use stor::config::TableConfig;
+use stor::db::rocks::{self, RockStore};
+use stor::types::{OwnedType, Str};
+use stor::Store;
+
+fn users(store: &Arc<RockStore>) -> Vec<(String, u64)> {
+    let users = store
+        .typed::<Str, OwnedType<u64>>("users", &TableConfig::default())
+        .unwrap();
+    store
+        .with_rtx(|rtx| users.range(rtx, &..)?.collect())
+        .unwrap()
+}
+
+fn put(store: &Arc<RockStore>, entries: &[(&str, u64)]) {
+    let users = store
+        .typed::<Str, OwnedType<u64>>("users", &TableConfig::default())
+        .unwrap();
+    store
+        .with_wtx(|wtx| {
+            for (name, age) in entries {
+                users.put(wtx, name, age)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn backup_and_restore() {
+    let (dir, backups, restored) = (
+        TempDir::new("backup-db"),
+        TempDir::new("backup-dir"),
+        TempDir::new("backup-restored"),
+    );
+    let store = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+
+    put(&store, &[("alice", 30), ("bob", 40)]);
+    let first = store.backup(backups.path()).unwrap();
+    let at_first = users(&store);
+
+    put(&store, &[("bob", 41), ("carol", 50)]);
+    let second = store.backup(backups.path()).unwrap();
+    let at_second = users(&store);
+    assert_ne!(first, second);
+    assert_eq!(rocks::backups(backups.path()).unwrap().len(), 2);
+
+    let open = |id| {
+        Arc::new(rocks::restore(backups.path(), id, restored.path(), &rocks_options(), []).unwrap())
+    };
+    assert_eq!(users(&open(Some(first))), at_first);
+    assert_eq!(users(&open(None)), at_second);
+
+    rocks::purge_backups(backups.path(), 1).unwrap();
+    let left = rocks::backups(backups.path()).unwrap();
+    assert_eq!(
+        left.iter().map(|b| b.backup_id).collect::<Vec<_>>(),
+        [second]
+    );
+}
+
+#[test]
+fn checkpoint_follows_store() {
+    let (dir, target) = (TempDir::new("checkpoint-db"), TempDir::new("checkpoint"));
+    let store = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+
+    put(&store, &[("alice", 30), ("bob", 40)]);
+    store.checkpoint(target.path()).unwrap();
+
+    // Updating the checkpoint removes entries deleted since.
+    let users_table = store
+        .typed::<Str, OwnedType<u64>>("users", &TableConfig::default())
+        .unwrap();
+    store
+        .with_wtx(|wtx| users_table.delete(wtx, "alice"))
+        .unwrap();
+    put(&store, &[("carol", 50)]);
+    store.checkpoint(target.path()).unwrap();
+
+    let copy = Arc::new(rocks::open(target.path(), &rocks_options(), []).unwrap());
+    assert_eq!(users(&copy), users(&store));
+}
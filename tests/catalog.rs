@@ -0,0 +1,161 @@
+//! Listing, dropping and renaming tables.
+mod common;
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::types::{OwnedType, Str};
+use stor::{Store, Transaction, Typed};
+
+fn users<S: Store>(store: &Arc<S>, name: &str) -> Typed<S, Str, OwnedType<u64>> {
+    store.typed(name, &TableConfig::default()).unwrap()
+}
+
+/// Fills `from`, renames it to `to`, and checks that all entries moved.
+fn rename<S: Store>(store: &Arc<S>, count: u64)
+where
+    S::Error: std::fmt::Debug,
+{
+    let from = users(store, "from");
+    store
+        .with_wtx(|wtx| {
+            for i in 0..count {
+                from.put(wtx, &format!("user{i:08}"), &i)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    store
+        .rename_table("from", "to", &TableConfig::default())
+        .unwrap();
+
+    let tables = store.list_tables().unwrap();
+    assert!(tables.contains(&"to".to_string()));
+    assert!(!tables.contains(&"from".to_string()));
+
+    let to = users(store, "to");
+    let entries = store
+        .with_rtx(|rtx| to.range(rtx, &..)?.collect::<Result<Vec<_>, _>>())
+        .unwrap();
+    assert_eq!(entries.len() as u64, count);
+    assert!(entries
+        .iter()
+        .enumerate()
+        .all(|(i, (k, v))| *k == format!("user{i:08}") && *v == i as u64));
+
+    // Renaming onto an existing table fails, and keeps both tables.
+    users(store, "other");
+    assert!(store
+        .rename_table("to", "other", &TableConfig::default())
+        .is_err());
+    let tables = store.list_tables().unwrap();
+    assert!(tables.contains(&"to".to_string()) && tables.contains(&"other".to_string()));
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::{MemError, MemStore};
+
+    #[test]
+    fn list_drop_rename() {
+        let store = Arc::new(MemStore::new());
+        users(&store, "a");
+        users(&store, "b");
+        assert_eq!(store.list_tables().unwrap(), ["a", "b"]);
+
+        store.drop_table("a").unwrap();
+        assert_eq!(store.list_tables().unwrap(), ["b"]);
+        assert!(matches!(
+            store.drop_table("a"),
+            Err(MemError::TableNotFound(_))
+        ));
+
+        rename(&store, 100);
+    }
+
+    #[test]
+    fn catalog_changes_within_write_transaction_fail() {
+        let store = Arc::new(MemStore::new());
+        users(&store, "a");
+
+        let wtx = store.wtx().unwrap();
+        assert!(matches!(store.drop_table("a"), Err(MemError::NestedWrite)));
+        assert!(matches!(
+            store.rename_table("a", "b", &TableConfig::default()),
+            Err(MemError::NestedWrite)
+        ));
+        assert!(matches!(store.wtx(), Err(MemError::NestedWrite)));
+        drop(wtx);
+
+        store.drop_table("a").unwrap();
+    }
+
+    #[test]
+    fn stale_handle_does_not_recreate_table() {
+        let store = Arc::new(MemStore::new());
+        let stale = users(&store, "a");
+        store.drop_table("a").unwrap();
+
+        let mut wtx = store.wtx().unwrap();
+        assert!(matches!(
+            stale.put(&mut wtx, "alice", &1),
+            Err(MemError::TableNotFound(_))
+        ));
+        assert!(matches!(
+            stale.clear(&mut wtx),
+            Err(MemError::TableNotFound(_))
+        ));
+        wtx.commit().unwrap();
+
+        assert!(store.list_tables().unwrap().is_empty());
+    }
+
+    #[test]
+    fn table_created_during_write_transaction() {
+        let store = Arc::new(MemStore::new());
+
+        let mut wtx = store.wtx().unwrap();
+        let created = users(&store, "late");
+        created.put(&mut wtx, "alice", &1).unwrap();
+        wtx.commit().unwrap();
+
+        let alice = store.with_rtx(|rtx| created.get(rtx, "alice")).unwrap();
+        assert_eq!(alice, Some(1));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks;
+
+    #[test]
+    fn list_drop_rename() {
+        let dir = TempDir::new("catalog");
+        let store = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+        users(&store, "a");
+        users(&store, "b");
+        assert_eq!(store.list_tables().unwrap(), ["a", "b"]);
+
+        store.drop_table("a").unwrap();
+        assert_eq!(store.list_tables().unwrap(), ["b"]);
+        assert!(store.drop_table("a").is_err());
+
+        // More than one copy batch.
+        rename(&store, 200_000);
+    }
+
+    #[test]
+    fn rename_missing_table_creates_nothing() {
+        let dir = TempDir::new("catalog");
+        let store = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+
+        assert!(store
+            .rename_table("missing", "to", &TableConfig::default())
+            .is_err());
+        assert!(store.list_tables().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+//! Change data capture through [`stor::changelog::ChangeLog`].
+mod common;
+
+use std::sync::Arc;
+
+use stor::changelog::{ChangeLog, ChangeLogError};
+use stor::config::TableConfig;
+use stor::types::{ByteSlice, OwnedType, Str};
+use stor::Store;
+
+fn replay_reproduces_table<S: Store>(store: &Arc<S>) {
+    let cfg = TableConfig::default();
+    let log = Arc::new(ChangeLog::open(store, "log", &cfg).unwrap());
+    let users = log.capture(store.typed::<Str, OwnedType<u64>>("users", &cfg).unwrap());
+
+    store
+        .with_wtx(|wtx| {
+            for i in 0..20u64 {
+                users.put(wtx, &format!("user{i:02}"), &i)?;
+            }
+            users.put(wtx, "user03", &300)?;
+            users.delete(wtx, "user04")?;
+            users.delete(wtx, "missing")
+        })
+        .unwrap();
+    store
+        .with_wtx(|wtx| {
+            users.clear(wtx)?;
+            users.put(wtx, "user01", &100)?;
+            users.put(wtx, "user21", &21)
+        })
+        .unwrap();
+
+    // Applies all changes to an empty table.
+    let replica = store
+        .typed::<ByteSlice, ByteSlice>("replica", &cfg)
+        .unwrap();
+    let changes = log.read(&store.rtx().unwrap(), 0, usize::MAX).unwrap();
+    assert_eq!(changes.len(), 20 + 2 + 19 + 2);
+    assert!(changes.windows(2).all(|w| w[0].seq + 1 == w[1].seq));
+
+    store
+        .with_wtx(|wtx| {
+            for change in &changes {
+                assert_eq!(change.table, "users");
+                match &change.new {
+                    Some(new) => replica.put(wtx, &change.key, new)?,
+                    None => replica.delete(wtx, &change.key)?,
+                }
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let raw = store.typed::<ByteSlice, ByteSlice>("users", &cfg).unwrap();
+    let (source, copy) = store
+        .with_rtx(|rtx| {
+            Ok((
+                raw.range(rtx, &..)?.collect::<Result<Vec<_>, _>>()?,
+                replica.range(rtx, &..)?.collect::<Result<Vec<_>, _>>()?,
+            ))
+        })
+        .unwrap();
+    assert_eq!(source.len(), 2);
+    assert_eq!(copy, source);
+}
+
+fn malformed_changes_are_errors<S: Store>(store: &Arc<S>) {
+    let cfg = TableConfig::default();
+    let log = Arc::new(ChangeLog::open(store, "log", &cfg).unwrap());
+    let users = log.capture(store.typed::<Str, OwnedType<u64>>("users", &cfg).unwrap());
+    store.with_wtx(|wtx| users.put(wtx, "alice", &1)).unwrap();
+
+    // Written past the log, under a sequence number and under a key that is not one.
+    let raw = store.typed::<ByteSlice, ByteSlice>("log", &cfg).unwrap();
+    let seq = u64::MAX.to_be_bytes();
+    store.with_wtx(|wtx| raw.put(wtx, &seq, b"\x01")).unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(log.read(&rtx, 0, 1).unwrap().len(), 1);
+    assert!(matches!(
+        log.read(&rtx, 0, usize::MAX),
+        Err(ChangeLogError::Malformed { key }) if key == seq
+    ));
+    drop(rtx);
+
+    store
+        .with_wtx(|wtx| {
+            raw.delete(wtx, &seq)?;
+            raw.put(wtx, b"seq", b"")
+        })
+        .unwrap();
+    let consumer = log.consumer("search").unwrap();
+    assert!(matches!(
+        consumer.poll(&store.rtx().unwrap(), 10),
+        Err(ChangeLogError::Malformed { key }) if key == b"seq"
+    ));
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::MemStore;
+
+    #[test]
+    fn replay_reproduces_table() {
+        super::replay_reproduces_table(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn malformed_changes_are_errors() {
+        super::malformed_changes_are_errors(&Arc::new(MemStore::new()));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks;
+
+    #[test]
+    fn replay_reproduces_table() {
+        let dir = TempDir::new("changelog");
+        let store = rocks::open(dir.path(), &rocks_options(), []).unwrap();
+        super::replay_reproduces_table(&Arc::new(store));
+    }
+
+    #[test]
+    fn malformed_changes_are_errors() {
+        let dir = TempDir::new("changelog");
+        let store = rocks::open(dir.path(), &rocks_options(), []).unwrap();
+        super::malformed_changes_are_errors(&Arc::new(store));
+    }
+}
@@ -0,0 +1,46 @@
+//! Runs the [`stor::testing`] conformance suite against the built-in backends.
+#![cfg(feature = "testing")]
+
+#[cfg(feature = "impl-rocks")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "impl-mem")]
+#[test]
+fn mem() {
+    stor::testing::conformance(|| Arc::new(stor::db::mem::MemStore::new()));
+}
+
+#[cfg(feature = "impl-mem")]
+#[test]
+fn mem_model() {
+    stor::testing::model(|| Arc::new(stor::db::mem::MemStore::new()));
+}
+
+/// Factory of empty RocksDB stores in subdirectories of `dir`.
+#[cfg(feature = "impl-rocks")]
+fn rocks_stores(dir: &std::path::Path) -> impl Fn() -> Arc<stor::db::rocks::RockStore> + '_ {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    move || {
+        let path = dir.join(NEXT.fetch_add(1, Ordering::Relaxed).to_string());
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        Arc::new(stor::db::rocks::open(path, &opts, []).unwrap())
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+#[test]
+fn rocks() {
+    let dir = std::env::temp_dir().join(format!("stor-conformance-{}", std::process::id()));
+    stor::testing::conformance(rocks_stores(&dir));
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[cfg(feature = "impl-rocks")]
+#[test]
+fn rocks_model() {
+    let dir = std::env::temp_dir().join(format!("stor-model-{}", std::process::id()));
+    stor::testing::model(rocks_stores(&dir));
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,95 @@
+//! JSON lines dumps of typed tables.
+#![cfg(all(feature = "impl-mem", feature = "format-json"))]
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use stor::config::TableConfig;
+use stor::db::mem::MemStore;
+use stor::dump::{dump, load, DumpError};
+use stor::types::{SerdeJson, Str};
+use stor::{Store, Typed};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    tags: Vec<String>,
+    age: Option<u32>,
+}
+
+type Users = Typed<MemStore, Str, SerdeJson<User>>;
+
+fn users(store: &Arc<MemStore>, name: &str) -> Users {
+    store.typed(name, &TableConfig::default()).unwrap()
+}
+
+fn entries(store: &Arc<MemStore>, users: &Users) -> Vec<(String, User)> {
+    store
+        .with_rtx(|rtx| users.range(rtx, &..)?.collect())
+        .unwrap()
+}
+
+#[test]
+fn dump_and_load() {
+    let store = Arc::new(MemStore::new());
+    let source = users(&store, "users");
+    store
+        .with_wtx(|wtx| {
+            for i in 0..10u32 {
+                let user = User {
+                    name: format!("User \"{i}\"\n"),
+                    tags: vec!["a".to_string(); i as usize % 3],
+                    age: (i % 2 == 0).then_some(i),
+                };
+                source.put(wtx, &format!("user{i}"), &user)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let mut all = vec![];
+    let mut part = vec![];
+    store
+        .with_rtx(|rtx| {
+            assert_eq!(dump(&source, rtx, &.., &mut all).unwrap(), 10);
+            let range = (
+                std::ops::Bound::Included("user3"),
+                std::ops::Bound::Excluded("user6"),
+            );
+            assert_eq!(dump(&source, rtx, &range, &mut part).unwrap(), 3);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(String::from_utf8(all.clone()).unwrap().lines().count(), 10);
+
+    let copy = users(&store, "copy");
+    assert_eq!(
+        store
+            .with_wtx(|wtx| Ok(load(&copy, wtx, &all[..]).unwrap()))
+            .unwrap(),
+        10
+    );
+    assert_eq!(entries(&store, &copy), entries(&store, &source));
+
+    let partial = users(&store, "partial");
+    store
+        .with_wtx(|wtx| Ok(load(&partial, wtx, &part[..]).unwrap()))
+        .unwrap();
+    let keys = entries(&store, &partial)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, ["user3", "user4", "user5"]);
+}
+
+#[test]
+fn invalid_line_is_reported() {
+    let store = Arc::new(MemStore::new());
+    let users = users(&store, "users");
+    let text =
+        "{\"key\":\"a\",\"value\":{\"name\":\"A\",\"tags\":[],\"age\":null}}\n\n{\"key\":\"b\"}\n";
+
+    let mut wtx = store.wtx().unwrap();
+    let loaded = load(&users, &mut wtx, text.as_bytes());
+    assert!(matches!(loaded, Err(DumpError::Json { line: 3, .. })));
+}
@@ -0,0 +1,214 @@
+//! Tables whose entries expire.
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use stor::config::TableConfig;
+use stor::expiring::Expiring;
+use stor::types::{ByteSlice, Str};
+use stor::Store;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+fn sessions<S: Store>(store: &Arc<S>) -> Expiring<S, Str, Str> {
+    Expiring::open(store, "sessions", &TableConfig::default()).unwrap()
+}
+
+/// Stores `key0` to `key9`, of which the even ones expire right away.
+fn fill<S: Store>(store: &Arc<S>, sessions: &Expiring<S, Str, Str>) {
+    store
+        .with_wtx(|wtx| {
+            for i in 0..10 {
+                let ttl = if i % 2 == 0 { Duration::ZERO } else { HOUR };
+                sessions.put(wtx, &format!("key{i}"), &format!("token{i}"), ttl)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+}
+
+fn live() -> Vec<String> {
+    (1..10).step_by(2).map(|i| format!("key{i}")).collect()
+}
+
+/// Number of entries physically stored in the table, including expired ones.
+fn stored<S: Store>(store: &Arc<S>) -> usize {
+    let raw = store
+        .typed::<Str, ByteSlice>("sessions", &TableConfig::default())
+        .unwrap();
+    store.with_rtx(|rtx| raw.len(rtx)).unwrap()
+}
+
+fn expired_are_invisible<S: Store>(store: &Arc<S>) {
+    let sessions = sessions(store);
+    fill(store, &sessions);
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(sessions.get(&rtx, "key0").unwrap(), None);
+    assert_eq!(
+        sessions.get(&rtx, "key1").unwrap().as_deref(),
+        Some("token1")
+    );
+
+    let keys = sessions
+        .range(&rtx, &..)
+        .unwrap()
+        .map(|item| item.map(|(key, _)| key))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(keys, live());
+
+    let mut keys = sessions
+        .rev_range(&rtx, &..)
+        .unwrap()
+        .map(|item| item.map(|(key, _)| key))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    keys.reverse();
+    assert_eq!(keys, live());
+    drop(rtx);
+
+    // Still stored until swept.
+    assert_eq!(stored(store), 10);
+}
+
+fn sweep<S: Store>(store: &Arc<S>) {
+    let sessions = sessions(store);
+    fill(store, &sessions);
+
+    // Every call examines at most 4 entries, whether they expired or not.
+    let mut calls = 0;
+    let mut cursor = None;
+    loop {
+        cursor = store
+            .with_wtx(|wtx| sessions.sweep(wtx, cursor.as_deref(), 4))
+            .unwrap();
+        calls += 1;
+        if calls == 1 {
+            assert_eq!(cursor.as_deref(), Some(&b"key3"[..]));
+            assert_eq!(stored(store), 8);
+        }
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(calls, 3);
+    assert_eq!(stored(store), 5);
+
+    let rtx = store.rtx().unwrap();
+    let count = sessions.range(&rtx, &..).unwrap().count();
+    assert_eq!(count, 5);
+}
+
+fn sweep_all<S: Store>(store: &Arc<S>) {
+    let sessions = sessions(store);
+    fill(store, &sessions);
+
+    sessions.sweep_all(3).unwrap();
+    assert_eq!(stored(store), 5);
+}
+
+fn sweeper<S: Store>(store: &Arc<S>) {
+    let sessions = Arc::new(sessions(store));
+    fill(store, &sessions);
+
+    let sweeper = sessions.spawn_sweeper(Duration::from_millis(10), 3);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while stored(store) > 5 {
+        assert!(Instant::now() < deadline, "expired entries were not swept");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    drop(sweeper);
+
+    assert_eq!(stored(store), 5);
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::MemStore;
+
+    #[test]
+    fn expired_are_invisible() {
+        super::expired_are_invisible(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn sweep() {
+        super::sweep(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn sweep_all() {
+        super::sweep_all(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn sweeper() {
+        super::sweeper(&Arc::new(MemStore::new()));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use rocksdb::{ColumnFamilyDescriptor, IteratorMode, DB};
+    use stor::db::rocks::{self, table_options, RockStore};
+
+    fn open(dir: &TempDir) -> Arc<RockStore> {
+        Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap())
+    }
+
+    #[test]
+    fn expired_are_invisible() {
+        let dir = TempDir::new("expiring");
+        super::expired_are_invisible(&open(&dir));
+    }
+
+    #[test]
+    fn sweep() {
+        let dir = TempDir::new("expiring");
+        super::sweep(&open(&dir));
+    }
+
+    #[test]
+    fn sweep_all() {
+        let dir = TempDir::new("expiring");
+        super::sweep_all(&open(&dir));
+    }
+
+    #[test]
+    fn sweeper() {
+        let dir = TempDir::new("expiring");
+        super::sweeper(&open(&dir));
+    }
+
+    #[test]
+    fn compaction_removes_expired() {
+        let dir = TempDir::new("expiring");
+        let store = open(&dir);
+        fill(&store, &sessions(&store));
+        drop(store);
+
+        // Transaction databases can not be compacted on demand, the plain database can.
+        let expiring = TableConfig {
+            expiring: true,
+            ..Default::default()
+        };
+        let cfs = [ColumnFamilyDescriptor::new(
+            "sessions",
+            table_options(&expiring),
+        )];
+        let db = DB::open_cf_descriptors(&rocks_options(), dir.path(), cfs).unwrap();
+        let cf = db.cf_handle("sessions").unwrap();
+        db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+
+        let keys = db
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(|item| String::from_utf8(item.unwrap().0.to_vec()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, live());
+    }
+}
@@ -0,0 +1,190 @@
+//! Backend-independent export and import of tables.
+mod common;
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::export::{export, import, ExportError};
+use stor::types::{OwnedType, Str};
+use stor::Store;
+
+/// More entries than are imported per transaction.
+const USERS: u64 = 25_000;
+
+fn fill<S: Store>(store: &Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let cfg = TableConfig::default();
+    let users = store.typed::<Str, OwnedType<u64>>("users", &cfg).unwrap();
+    let empty = store.typed::<Str, OwnedType<u64>>("empty", &cfg).unwrap();
+    store
+        .with_wtx(|wtx| {
+            for i in 0..USERS {
+                users.put(wtx, &format!("user{i:05}"), &i)?;
+            }
+            empty.clear(wtx)
+        })
+        .unwrap();
+}
+
+fn entries<S: Store>(store: &Arc<S>, name: &str) -> Vec<(String, u64)>
+where
+    S::Error: std::fmt::Debug,
+{
+    let table = store
+        .typed::<Str, OwnedType<u64>>(name, &TableConfig::default())
+        .unwrap();
+    store
+        .with_rtx(|rtx| table.range(rtx, &..)?.collect())
+        .unwrap()
+}
+
+/// Exports `from`, imports it into `to` over existing data, and compares both.
+fn round_trip<S1: Store, S2: Store>(from: &Arc<S1>, to: &Arc<S2>)
+where
+    S1::Error: std::fmt::Debug,
+    S2::Error: std::fmt::Debug,
+{
+    fill(from);
+    let stale = to
+        .typed::<Str, OwnedType<u64>>("users", &TableConfig::default())
+        .unwrap();
+    to.with_wtx(|wtx| stale.put(wtx, "stale", &1)).unwrap();
+
+    let mut file = vec![];
+    assert_eq!(export(from, &["users", "empty"], &mut file).unwrap(), USERS);
+    assert_eq!(import(to, &file[..], []).unwrap(), USERS);
+
+    assert_eq!(entries(to, "users"), entries(from, "users"));
+    assert!(entries(to, "empty").is_empty());
+
+    let mut tables = to.list_tables().unwrap();
+    tables.sort();
+    assert_eq!(tables, ["empty", "users"]);
+}
+
+/// A damaged file leaves the store as it was.
+fn corrupt<S: Store>(store: &Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    fill(store);
+    let mut file = vec![];
+    export(store, &["users"], &mut file).unwrap();
+
+    let users = store
+        .typed::<Str, OwnedType<u64>>("users", &TableConfig::default())
+        .unwrap();
+    store
+        .with_wtx(|wtx| users.put(wtx, "user00000", &42))
+        .unwrap();
+    let before = entries(store, "users");
+
+    let mut damaged = file.clone();
+    let middle = damaged.len() / 2;
+    damaged[middle] ^= 0xFF;
+    assert!(matches!(
+        import(store, &damaged[..], []),
+        Err(ExportError::Corrupt(_))
+    ));
+
+    let truncated = &file[..file.len() - 1];
+    assert!(matches!(
+        import(store, truncated, []),
+        Err(ExportError::Io(_))
+    ));
+
+    assert_eq!(entries(store, "users"), before);
+    let mut tables = store.list_tables().unwrap();
+    tables.sort();
+    assert_eq!(tables, ["empty", "users"]);
+}
+
+fn unknown_table<S: Store>(store: &Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let exported = export(store, &["missing"], vec![]);
+    assert!(matches!(exported, Err(ExportError::UnknownTable(name)) if name == "missing"));
+    assert!(store.list_tables().unwrap().is_empty());
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::MemStore;
+    use stor::merge::{Counter, MergeOperator};
+
+    #[test]
+    fn round_trip() {
+        super::round_trip(&Arc::new(MemStore::new()), &Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn corrupt() {
+        super::corrupt(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn unknown_table() {
+        super::unknown_table(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn imported_tables_get_their_config() {
+        let from = Arc::new(MemStore::new());
+        let to = Arc::new(MemStore::new());
+        let cfg = TableConfig {
+            merge: Some(MergeOperator::of::<Counter>()),
+            ..Default::default()
+        };
+
+        let visits = from.typed::<Str, Counter>("visits", &cfg).unwrap();
+        from.with_wtx(|wtx| visits.merge(wtx, "/", &1)).unwrap();
+        let mut file = vec![];
+        export(&from, &["visits"], &mut file).unwrap();
+        import(&to, &file[..], [("visits", &cfg)]).unwrap();
+
+        let visits = to.typed::<Str, Counter>("visits", &cfg).unwrap();
+        to.with_wtx(|wtx| visits.merge(wtx, "/", &2)).unwrap();
+        assert_eq!(to.with_rtx(|rtx| visits.get(rtx, "/")).unwrap(), Some(3));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks::{self, RockStore};
+
+    fn open(dir: &TempDir) -> Arc<RockStore> {
+        Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap())
+    }
+
+    #[cfg(feature = "impl-mem")]
+    #[test]
+    fn mem_to_rocks() {
+        let dir = TempDir::new("export");
+        super::round_trip(&Arc::new(stor::db::mem::MemStore::new()), &open(&dir));
+    }
+
+    #[cfg(feature = "impl-mem")]
+    #[test]
+    fn rocks_to_mem() {
+        let dir = TempDir::new("export");
+        super::round_trip(&open(&dir), &Arc::new(stor::db::mem::MemStore::new()));
+    }
+
+    #[test]
+    fn corrupt() {
+        let dir = TempDir::new("export");
+        super::corrupt(&open(&dir));
+    }
+
+    #[test]
+    fn unknown_table() {
+        let dir = TempDir::new("export");
+        super::unknown_table(&open(&dir));
+    }
+}
@@ -0,0 +1,205 @@
+//! Secondary indexes kept up to date by [`stor::index::Indexed`].
+mod common;
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::index::{IndexError, Indexed};
+use stor::types::{ByteSlice, OwnedType};
+use stor::{Store, Transaction};
+
+/// Buckets of the `by_bucket` index.
+const BUCKETS: u64 = 5;
+
+type Values<S> = Indexed<S, OwnedType<u64>, OwnedType<u64>>;
+
+fn values<S: Store>(store: &Arc<S>) -> Values<S>
+where
+    S::Error: std::fmt::Debug,
+{
+    let cfg = TableConfig::default();
+    Indexed::new(store.typed("values", &cfg).unwrap())
+        .index::<OwnedType<u64>, u64, _, _>(store, "by_bucket", &cfg, |v: &u64| v % BUCKETS)
+        .unwrap()
+}
+
+/// Checks that the index holds exactly one entry for every value of the primary table.
+fn check_index<S: Store>(store: &Arc<S>, values: &Values<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let entries = store
+        .typed::<ByteSlice, ByteSlice>("by_bucket", &TableConfig::default())
+        .unwrap();
+    let rtx = store.rtx().unwrap();
+
+    let primary = values
+        .range(&rtx, &..)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries.len(&rtx).unwrap(), primary.len());
+
+    for bucket in 0..BUCKETS {
+        let mut found = values
+            .get_by_index::<OwnedType<u64>, u64>(&rtx, "by_bucket", &bucket)
+            .unwrap();
+        found.sort();
+        let expected = primary
+            .iter()
+            .filter(|(_, v)| v % BUCKETS == bucket)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected, "bucket {bucket}");
+    }
+}
+
+/// Two writers repeatedly overwriting and deleting the same keys.
+fn concurrent_writers<S: Store>(store: Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let values = values(&store);
+
+    std::thread::scope(|scope| {
+        for writer in 0..2u64 {
+            let (store, values) = (&store, &values);
+            scope.spawn(move || {
+                for i in 0..500u64 {
+                    let mut wtx = store.wtx().unwrap();
+                    if i % 7 == 0 {
+                        values.delete(&mut wtx, &(i % 20)).unwrap();
+                    } else {
+                        values.put(&mut wtx, &(i % 20), &(i * 2 + writer)).unwrap();
+                    }
+                    wtx.commit().unwrap();
+                }
+            });
+        }
+    });
+
+    check_index(&store, &values);
+}
+
+fn unknown_index<S: Store>(store: Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let values = values(&store);
+    let rtx = store.rtx().unwrap();
+
+    let found = values.get_by_index::<OwnedType<u64>, u64>(&rtx, "by_name", &1);
+    assert!(matches!(found, Err(IndexError::UnknownIndex { index }) if index == "by_name"));
+}
+
+/// Values must be unique, in addition to the `by_bucket` index.
+fn unique_values<S: Store>(store: &Arc<S>) -> Values<S>
+where
+    S::Error: std::fmt::Debug,
+{
+    values(store)
+        .unique_index::<OwnedType<u64>, u64, _, _>(
+            store,
+            "by_value",
+            &TableConfig::default(),
+            |v: &u64| *v,
+        )
+        .unwrap()
+}
+
+fn unique_violation_writes_nothing<S: Store>(store: Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let values = unique_values(&store);
+    let put = |key: u64, value: u64| {
+        let mut wtx = store.wtx().unwrap();
+        values.put(&mut wtx, &key, &value)?;
+        wtx.commit().unwrap();
+        Ok::<_, IndexError<S::Error>>(())
+    };
+
+    put(1, 10).unwrap();
+    let conflict = put(2, 10);
+    assert!(matches!(conflict, Err(IndexError::Unique { index }) if index == "by_value"));
+
+    // Neither the primary table, nor the other index saw the rejected value.
+    let rtx = store.rtx().unwrap();
+    assert_eq!(values.get(&rtx, &2).unwrap(), None);
+    check_index(&store, &values);
+    drop(rtx);
+
+    // Rewriting the same value under its own key is not a violation, moving it away frees it.
+    put(1, 10).unwrap();
+    put(1, 11).unwrap();
+    put(2, 10).unwrap();
+
+    let rtx = store.rtx().unwrap();
+    let found = values
+        .get_by_index::<OwnedType<u64>, u64>(&rtx, "by_value", &10)
+        .unwrap();
+    assert_eq!(found, [(2, 10)]);
+    drop(rtx);
+    check_index(&store, &values);
+
+    // Rebuilding fails for values written past the unique index.
+    let plain = self::values(&store);
+    let mut wtx = store.wtx().unwrap();
+    plain.put(&mut wtx, &3, &11).unwrap();
+    assert!(matches!(
+        values.reindex(&mut wtx, "by_value"),
+        Err(IndexError::Unique { .. })
+    ));
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::MemStore;
+
+    #[test]
+    fn concurrent_writers() {
+        super::concurrent_writers(Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn unknown_index() {
+        super::unknown_index(Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn unique_violation_writes_nothing() {
+        super::unique_violation_writes_nothing(Arc::new(MemStore::new()));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks;
+
+    #[test]
+    fn concurrent_writers() {
+        let dir = TempDir::new("index");
+        super::concurrent_writers(Arc::new(
+            rocks::open(dir.path(), &rocks_options(), []).unwrap(),
+        ));
+    }
+
+    #[test]
+    fn unknown_index() {
+        let dir = TempDir::new("index");
+        super::unknown_index(Arc::new(
+            rocks::open(dir.path(), &rocks_options(), []).unwrap(),
+        ));
+    }
+
+    #[test]
+    fn unique_violation_writes_nothing() {
+        let dir = TempDir::new("index");
+        super::unique_violation_writes_nothing(Arc::new(
+            rocks::open(dir.path(), &rocks_options(), []).unwrap(),
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+//! Merge formats, on their own and applied by the backends.
+mod common;
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::format::{DFormat, EFormat};
+use stor::merge::{Append, Counter, Max, MergeFormat, MergeOperator, SetUnion};
+use stor::types::{BigEndian, Str};
+use stor::{Store, Transaction};
+
+type Highest = Max<BigEndian<u64>>;
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+/// Merges `operands` onto `existing` one by one, and as a single operand combined from all of
+/// them without the value, the way backends merge partially. Both must give the same result.
+fn merged<M: MergeFormat + DFormat>(existing: Option<&[u8]>, operands: &[&[u8]]) -> M::DItem {
+    let full = M::merge(existing, &mut operands.iter().copied()).unwrap();
+    let partial = M::merge(None, &mut operands.iter().copied()).unwrap();
+    let applied = M::merge(existing, &mut Some(&partial[..]).into_iter()).unwrap();
+    assert_eq!(full, applied, "partial merge");
+
+    M::decode(&full).unwrap()
+}
+
+#[test]
+fn max() {
+    let [one, three, five, nine, twelve] =
+        [1, 3, 5, 9, 12].map(|i| Highest::encode(&i).into_owned());
+    let operands = [&five[..], &nine, &three];
+
+    assert_eq!(merged::<Highest>(None, &operands), 9);
+    assert_eq!(merged::<Highest>(Some(&one), &operands), 9);
+    assert_eq!(merged::<Highest>(Some(&twelve), &operands), 12);
+    assert!(Highest::merge(Some(&one), &mut [&b"bad"[..]].into_iter()).is_none());
+}
+
+#[test]
+fn append() {
+    let [a, bc, x] =
+        [&["a"][..], &["b", "c"], &["x"]].map(|i| Append::<Str>::encode(&strings(i)).into_owned());
+    let operands = [&a[..], &bc];
+
+    assert_eq!(merged::<Append<Str>>(None, &operands), ["a", "b", "c"]);
+    assert_eq!(
+        merged::<Append<Str>>(Some(&x), &operands),
+        ["x", "a", "b", "c"]
+    );
+    assert!(Append::<Str>::merge(Some(&x), &mut [&[9, 0, 0, 0][..]].into_iter()).is_none());
+}
+
+#[test]
+fn set_union() {
+    let [ba, ac, da] = [&["b", "a"][..], &["a", "c"], &["d", "a"]]
+        .map(|i| SetUnion::<Str>::encode(&strings(i)).into_owned());
+    let operands = [&ba[..], &ac];
+
+    assert_eq!(merged::<SetUnion<Str>>(None, &operands), ["a", "b", "c"]);
+    assert_eq!(
+        merged::<SetUnion<Str>>(Some(&da), &operands),
+        ["a", "b", "c", "d"]
+    );
+    assert!(SetUnion::<Str>::merge(Some(&da), &mut [&[9, 0, 0, 0][..]].into_iter()).is_none());
+}
+
+fn config<M: MergeFormat>() -> TableConfig {
+    TableConfig {
+        merge: Some(MergeOperator::of::<M>()),
+        ..Default::default()
+    }
+}
+
+/// Every accumulator merges onto missing and stored values, within and across transactions.
+fn accumulators<S: Store>(store: &Arc<S>) {
+    let highest = store
+        .typed::<Str, Highest>("highest", &config::<Highest>())
+        .unwrap();
+    let log = store
+        .typed::<Str, Append<Str>>("log", &config::<Append<Str>>())
+        .unwrap();
+    let tags = store
+        .typed::<Str, SetUnion<Str>>("tags", &config::<SetUnion<Str>>())
+        .unwrap();
+
+    let mut wtx = store.wtx().unwrap();
+    highest.put(&mut wtx, "stored", &10).unwrap();
+    log.put(&mut wtx, "stored", &strings(&["x"])).unwrap();
+    tags.put(&mut wtx, "stored", &strings(&["d", "a"])).unwrap();
+    for key in ["new", "stored"] {
+        highest.merge(&mut wtx, key, &5).unwrap();
+        log.merge(&mut wtx, key, &strings(&["a"])).unwrap();
+        tags.merge(&mut wtx, key, &strings(&["b", "a"])).unwrap();
+    }
+    wtx.commit().unwrap();
+
+    store
+        .with_wtx(|wtx| {
+            for key in ["new", "stored"] {
+                highest.merge(wtx, key, &7)?;
+                log.merge(wtx, key, &strings(&["b", "c"]))?;
+                tags.merge(wtx, key, &strings(&["c"]))?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(highest.get(&rtx, "new").unwrap(), Some(7));
+    assert_eq!(highest.get(&rtx, "stored").unwrap(), Some(10));
+    assert_eq!(log.get(&rtx, "new").unwrap().unwrap(), ["a", "b", "c"]);
+    assert_eq!(
+        log.get(&rtx, "stored").unwrap().unwrap(),
+        ["x", "a", "b", "c"]
+    );
+    assert_eq!(tags.get(&rtx, "new").unwrap().unwrap(), ["a", "b", "c"]);
+    assert_eq!(
+        tags.get(&rtx, "stored").unwrap().unwrap(),
+        ["a", "b", "c", "d"]
+    );
+}
+
+/// Merges into a table opened without an operator fail when they are written, and write nothing.
+fn without_operator<S: Store>(store: &Arc<S>) -> S::Error {
+    let visits = store
+        .typed::<Str, Counter>("visits", &TableConfig::default())
+        .unwrap();
+    let error = store
+        .with_wtx(|wtx| visits.merge(wtx, "/", &1))
+        .unwrap_err();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(visits.len(&rtx).unwrap(), 0);
+    error
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::{MemError, MemStore};
+
+    #[test]
+    fn accumulators() {
+        super::accumulators(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn without_operator() {
+        let error = super::without_operator(&Arc::new(MemStore::new()));
+        assert!(matches!(error, MemError::NoMergeOperator(table) if table == "visits"));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks::{self, RockError};
+
+    #[test]
+    fn accumulators() {
+        let dir = TempDir::new("merge");
+        let store = rocks::open(dir.path(), &rocks_options(), []).unwrap();
+        super::accumulators(&Arc::new(store));
+    }
+
+    #[test]
+    fn without_operator() {
+        let dir = TempDir::new("merge");
+        let store = rocks::open(dir.path(), &rocks_options(), []).unwrap();
+        let error = super::without_operator(&Arc::new(store));
+        assert!(matches!(error, RockError::NoMergeOperator(table) if table == "visits"));
+    }
+}
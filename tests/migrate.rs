@@ -0,0 +1,307 @@
+//! Schema migrations and value conversions.
+#![cfg(feature = "impl-mem")]
+
+use std::ops::Bound;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::db::mem::{MemError, MemStore};
+use stor::migrate::{convert_values, MigrationError, Migrations};
+use stor::types::{OwnedType, Str};
+use stor::{Store, Typed};
+
+type Counts = Typed<MemStore, Str, OwnedType<u64>>;
+
+fn counts(store: &Arc<MemStore>) -> Counts {
+    store.typed("counts", &TableConfig::default()).unwrap()
+}
+
+/// Migrations that count how many times each step ran.
+fn migrations(runs: &Arc<[AtomicUsize; 2]>) -> Migrations<MemStore> {
+    let (first, second) = (runs.clone(), runs.clone());
+    Migrations::new("app")
+        .step(move |store, wtx| {
+            first[0].fetch_add(1, Ordering::SeqCst);
+            counts(store).put(wtx, "first", &1)
+        })
+        .step(move |store, wtx| {
+            second[1].fetch_add(1, Ordering::SeqCst);
+            counts(store).put(wtx, "second", &2)
+        })
+}
+
+#[test]
+fn steps_run_once() {
+    let store = Arc::new(MemStore::new());
+    let runs = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+
+    assert_eq!(migrations(&runs).version(&store).unwrap(), 0);
+    assert_eq!(migrations(&runs).run(&store).unwrap(), 2);
+    assert_eq!(migrations(&runs).run(&store).unwrap(), 2);
+    assert_eq!(migrations(&runs).version(&store).unwrap(), 2);
+
+    assert_eq!(runs[0].load(Ordering::SeqCst), 1);
+    assert_eq!(runs[1].load(Ordering::SeqCst), 1);
+
+    let counts = counts(&store);
+    let second = store.with_rtx(|rtx| counts.get(rtx, "second")).unwrap();
+    assert_eq!(second, Some(2));
+}
+
+#[test]
+fn newer_version_is_rejected() {
+    let store = Arc::new(MemStore::new());
+    let runs = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+    migrations(&runs).run(&store).unwrap();
+
+    let older = Migrations::<MemStore>::new("app").step(|_, _| Ok(()));
+    assert!(matches!(
+        older.run(&store),
+        Err(MigrationError::UnknownVersion {
+            stored: 2,
+            known: 1
+        })
+    ));
+}
+
+#[test]
+fn paged_step_resumes_after_failure() {
+    let store = Arc::new(MemStore::new());
+    let counts = counts(&store);
+    store
+        .with_wtx(|wtx| {
+            for i in 0..10u64 {
+                counts.put(wtx, &format!("key{i}"), &i)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    // Doubles values in pages of 3 entries, failing once on the third page.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let make = |calls: Arc<AtomicUsize>| {
+        Migrations::<MemStore>::new("paged").paged_step(move |store, wtx, checkpoint| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 2 {
+                return Err(MemError::TableNotFound("interrupted".to_string()));
+            }
+
+            let counts = self::counts(store);
+            let last = checkpoint
+                .as_ref()
+                .map(|key| String::from_utf8(key.clone()).unwrap());
+            let start = match &last {
+                Some(key) => Bound::Excluded(key.as_str()),
+                None => Bound::Unbounded,
+            };
+            let page = counts
+                .range(wtx, &(start, Bound::Unbounded))?
+                .take(3)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (key, value) in &page {
+                counts.put(wtx, key, &(value * 2))?;
+            }
+            if let Some((last, _)) = page.last() {
+                *checkpoint = Some(last.clone().into_bytes());
+            }
+            Ok(())
+        })
+    };
+
+    assert!(make(calls.clone()).run(&store).is_err());
+    assert_eq!(make(calls.clone()).version(&store).unwrap(), 0);
+    assert_eq!(make(calls).run(&store).unwrap(), 1);
+
+    let values = store
+        .with_rtx(|rtx| counts.range(rtx, &..)?.collect::<Result<Vec<_>, _>>())
+        .unwrap();
+    assert_eq!(values.len(), 10);
+    for (key, value) in values {
+        let i: u64 = key.strip_prefix("key").unwrap().parse().unwrap();
+        assert_eq!(value, i * 2, "{key} converted exactly once");
+    }
+}
+
+#[test]
+fn conversion_resumes_after_interruption() {
+    let store = Arc::new(MemStore::new());
+    let small = counts(&store).remap_data_type::<OwnedType<u32>>();
+    store
+        .with_wtx(|wtx| {
+            for i in 0..10u32 {
+                small.put(wtx, &format!("key{i}"), &i)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let convert = |progress: &mut dyn FnMut(usize)| {
+        convert_values::<OwnedType<u64>, _, _, _, _, _>(
+            &store,
+            "counts",
+            self::counts(&store).remap_data_type::<OwnedType<u32>>(),
+            3,
+            |value| value as u64 + 1,
+            progress,
+        )
+    };
+
+    // Stops the conversion after the second batch has been committed.
+    let interrupted = catch_unwind(AssertUnwindSafe(|| {
+        convert(&mut |done| assert!(done < 6, "interrupted"))
+    }));
+    assert!(interrupted.is_err());
+
+    let mut reported = vec![];
+    convert(&mut |done| reported.push(done)).unwrap();
+    assert_eq!(reported, [9, 10]);
+
+    // A finished conversion is not repeated.
+    convert(&mut |_| panic!("converted again")).unwrap();
+
+    let counts = counts(&store);
+    let values = store
+        .with_rtx(|rtx| counts.range(rtx, &..)?.collect::<Result<Vec<_>, _>>())
+        .unwrap();
+    assert_eq!(values.len(), 10);
+    for (key, value) in values {
+        let i: u64 = key.strip_prefix("key").unwrap().parse().unwrap();
+        assert_eq!(value, i + 1, "{key} converted exactly once");
+    }
+}
+
+#[cfg(all(feature = "format-json", feature = "format-postcard"))]
+mod codecs {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use stor::types::{ByteSlice, Fallback, Postcard, SerdeJson};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        visits: u32,
+    }
+
+    fn user(i: u32) -> User {
+        User {
+            name: format!("user{i}"),
+            visits: i,
+        }
+    }
+
+    fn json(store: &Arc<MemStore>) -> Typed<MemStore, Str, SerdeJson<User>> {
+        let json = store
+            .typed::<Str, SerdeJson<User>>("users", &TableConfig::default())
+            .unwrap();
+        store
+            .with_wtx(|wtx| {
+                for i in 0..10 {
+                    json.put(wtx, &format!("key{i}"), &user(i))?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        json
+    }
+
+    fn entries(
+        store: &Arc<MemStore>,
+        users: &Typed<MemStore, Str, Postcard<User>>,
+    ) -> Vec<(String, User)> {
+        store
+            .with_rtx(|rtx| users.range(rtx, &..)?.collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn json_to_postcard_with_concurrent_writes() {
+        let store = Arc::new(MemStore::new());
+        let json = json(&store);
+
+        // Written by the service while the conversion runs, before and after its position.
+        let live = store
+            .typed::<Str, Fallback<Postcard<User>, SerdeJson<User>>>(
+                "users",
+                &TableConfig::default(),
+            )
+            .unwrap();
+        let mut batches = 0;
+        let users = convert_values::<Postcard<User>, _, _, _, _, _>(
+            &store,
+            "users",
+            json,
+            3,
+            |user| user,
+            |_| {
+                batches += 1;
+                if batches == 1 {
+                    store
+                        .with_wtx(|wtx| {
+                            live.put(wtx, "key0", &user(100))?;
+                            live.put(wtx, "key8", &user(200))
+                        })
+                        .unwrap();
+                    let old = store.with_rtx(|rtx| live.get(rtx, "key5")).unwrap();
+                    assert_eq!(old, Some(user(5)));
+                }
+            },
+        )
+        .unwrap();
+
+        let entries = entries(&store, &users);
+        assert_eq!(entries.len(), 10);
+        for (key, value) in entries {
+            let expected = match key.as_str() {
+                "key0" => user(100),
+                "key8" => user(200),
+                key => user(key.strip_prefix("key").unwrap().parse().unwrap()),
+            };
+            assert_eq!(value, expected, "{key}");
+        }
+    }
+
+    #[test]
+    fn undecodable_value_stops_conversion() {
+        let store = Arc::new(MemStore::new());
+        let json = json(&store);
+        let raw = json.clone().remap_data_type::<ByteSlice>();
+        store
+            .with_wtx(|wtx| raw.put(wtx, "key4", &[0xFF][..]))
+            .unwrap();
+
+        let convert = |json| {
+            convert_values::<Postcard<User>, _, _, _, _, _>(
+                &store,
+                "users",
+                json,
+                3,
+                |user| user,
+                |_| {},
+            )
+        };
+        assert!(matches!(
+            convert(json.clone()),
+            Err(MigrationError::Undecodable { key }) if key == b"key4"
+        ));
+
+        // The batch holding the value was not committed, and is converted once it is fixed.
+        let postcard = json.clone().remap_data_type::<Postcard<User>>();
+        let converted = store.with_rtx(|rtx| postcard.get(rtx, "key2")).unwrap();
+        assert_eq!(converted, Some(user(2)));
+        let untouched = store.with_rtx(|rtx| json.get(rtx, "key3")).unwrap();
+        assert_eq!(untouched, Some(user(3)));
+
+        store
+            .with_wtx(|wtx| json.put(wtx, "key4", &user(4)))
+            .unwrap();
+        let users = convert(json).unwrap();
+        assert_eq!(
+            entries(&store, &users),
+            (0..10)
+                .map(|i| (format!("key{i}"), user(i)))
+                .collect::<Vec<_>>()
+        );
+    }
+}
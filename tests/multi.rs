@@ -0,0 +1,79 @@
+//! Tables storing multiple values per key.
+mod common;
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::types::Str;
+use stor::Store;
+
+fn values_of_a_key<S: Store>(store: &Arc<S>) {
+    let tags = store
+        .multi_typed::<Str, Str>("tags", &TableConfig::default())
+        .unwrap();
+
+    store
+        .with_wtx(|wtx| {
+            tags.insert(wtx, "post", "rust")?;
+            tags.insert(wtx, "post", "db")?;
+            tags.insert(wtx, "post", "rust")?;
+            // Keys that are prefixes of each other, or contain zero bytes, stay apart.
+            tags.insert(wtx, "po", "short")?;
+            tags.insert(wtx, "post\0", "zero")?;
+            tags.insert(wtx, "postal", "mail")
+        })
+        .unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(tags.get_all(&rtx, "post").unwrap(), ["db", "rust"]);
+    assert_eq!(tags.count(&rtx, "post").unwrap(), 2);
+    assert_eq!(tags.get_all(&rtx, "po").unwrap(), ["short"]);
+    assert_eq!(tags.get_all(&rtx, "post\0").unwrap(), ["zero"]);
+    assert!(tags.contains(&rtx, "post", "db").unwrap());
+    assert!(!tags.contains(&rtx, "postal", "db").unwrap());
+    drop(rtx);
+
+    store
+        .with_wtx(|wtx| {
+            tags.remove(wtx, "post", "db")?;
+            tags.remove(wtx, "post", "missing")?;
+            tags.delete_all(wtx, "po")
+        })
+        .unwrap();
+
+    let rtx = store.rtx().unwrap();
+    assert_eq!(tags.get_all(&rtx, "post").unwrap(), ["rust"]);
+    assert_eq!(tags.count(&rtx, "po").unwrap(), 0);
+    assert_eq!(tags.get_all(&rtx, "postal").unwrap(), ["mail"]);
+    drop(rtx);
+
+    store.with_wtx(|wtx| tags.clear(wtx)).unwrap();
+    let rtx = store.rtx().unwrap();
+    assert_eq!(tags.count(&rtx, "post").unwrap(), 0);
+    assert_eq!(tags.count(&rtx, "postal").unwrap(), 0);
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::MemStore;
+
+    #[test]
+    fn values_of_a_key() {
+        super::values_of_a_key(&Arc::new(MemStore::new()));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks;
+
+    #[test]
+    fn values_of_a_key() {
+        let dir = TempDir::new("multi");
+        let store = rocks::open(dir.path(), &rocks_options(), []).unwrap();
+        super::values_of_a_key(&Arc::new(store));
+    }
+}
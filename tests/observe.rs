@@ -0,0 +1,237 @@
+//! Subscriptions and hooks notified about committed transactions.
+mod common;
+
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use stor::config::TableConfig;
+use stor::expiring::Expiring;
+use stor::format::{DFormat, EFormat};
+use stor::observe::{Event, Op, Subscription, Veto, Write};
+use stor::types::{OwnedType, Str};
+use stor::{Store, Typed};
+
+/// All changes delivered so far.
+fn received<KC: DFormat, DC: DFormat>(
+    sub: &Subscription<KC, DC>,
+) -> Vec<Event<KC::DItem, DC::DItem>> {
+    std::iter::from_fn(|| sub.try_recv()).collect()
+}
+
+fn visits<S: Store>(store: &Arc<S>) -> Typed<S, Str, OwnedType<u64>> {
+    store.typed("visits", &TableConfig::default()).unwrap()
+}
+
+fn subscriptions_see_committed_changes_in_range<S: Store>(store: &Arc<S>) {
+    let visits = visits(store);
+
+    let blog = store.subscribe_prefix::<Str, OwnedType<u64>>("visits", b"/blog/");
+    let range = store.subscribe::<Str, OwnedType<u64>, _>(
+        "visits",
+        &(Bound::Included("/a"), Bound::Excluded("/b")),
+    );
+
+    // Changes of transactions that do not commit are never delivered.
+    let mut wtx = store.wtx().unwrap();
+    visits.put(&mut wtx, "/blog/draft", &1).unwrap();
+    drop(wtx);
+
+    store
+        .with_wtx(|wtx| {
+            visits.put(wtx, "/blog/first", &1)?;
+            visits.put(wtx, "/about", &2)?;
+            visits.put(wtx, "/contact", &3)?;
+            visits.delete(wtx, "/blog/first")
+        })
+        .unwrap();
+
+    assert_eq!(
+        received(&blog),
+        [
+            Event::Put {
+                key: "/blog/first".to_string(),
+                value: 1
+            },
+            Event::Delete {
+                key: "/blog/first".to_string()
+            },
+        ]
+    );
+    assert_eq!(
+        received(&range),
+        [Event::Put {
+            key: "/about".to_string(),
+            value: 2
+        }]
+    );
+}
+
+fn dropped_subscriptions_are_pruned<S: Store>(store: &Arc<S>) {
+    let visits = visits(store);
+
+    let kept = store.subscribe_prefix::<Str, OwnedType<u64>>("visits", b"/kept");
+    let dropped = store.subscribe_prefix::<Str, OwnedType<u64>>("visits", b"/dropped");
+    drop(dropped);
+    assert!(store.observers().active());
+
+    store.with_wtx(|wtx| visits.put(wtx, "/kept", &1)).unwrap();
+    assert_eq!(
+        received(&kept),
+        [Event::Put {
+            key: "/kept".to_string(),
+            value: 1
+        }]
+    );
+
+    // Write transactions stop recording their writes as soon as the last one is dropped.
+    drop(kept);
+    assert!(!store.observers().active());
+}
+
+/// Values that do not decode in the formats of a subscription are reported, rather than lost.
+fn malformed_changes<S: Store>(store: &Arc<S>) {
+    let cfg = TableConfig::default();
+    let sessions = Expiring::<_, Str, Str>::open(store, "sessions", &cfg).unwrap();
+    let raw = store.subscribe_prefix::<Str, Str>("sessions", b"");
+
+    store
+        .with_wtx(|wtx| {
+            sessions.put(wtx, "alice", "token", Duration::from_secs(60))?;
+            sessions.delete(wtx, "alice")
+        })
+        .unwrap();
+    assert_eq!(
+        received(&raw),
+        [
+            Event::Malformed {
+                key: b"alice".to_vec()
+            },
+            Event::Delete {
+                key: "alice".to_string()
+            },
+        ]
+    );
+}
+
+/// Returns the error of the vetoed commit.
+fn vetoed_commits_are_aborted<S: Store>(store: &Arc<S>) -> S::Error {
+    let visits = visits(store);
+    let other = store
+        .typed::<Str, OwnedType<u64>>("other", &TableConfig::default())
+        .unwrap();
+
+    let committed = Arc::new(Mutex::new(vec![]));
+    let log = committed.clone();
+    store
+        .observers()
+        .on_post_commit(&["visits"], move |writes| {
+            log.lock().unwrap().extend(writes.iter().cloned())
+        });
+    let veto = store.observers().on_pre_commit(&["visits"], |writes| {
+        match writes.iter().any(|w| w.key.starts_with(b"/admin")) {
+            true => Err(Veto("admin pages are not counted".to_string())),
+            false => Ok(()),
+        }
+    });
+
+    let vetoed = store
+        .with_wtx(|wtx| {
+            visits.put(wtx, "/", &1)?;
+            visits.put(wtx, "/admin", &1)?;
+            other.put(wtx, "x", &1)
+        })
+        .unwrap_err();
+
+    // Nothing of the vetoed transaction is visible, and post-commit hooks did not run.
+    let rtx = store.rtx().unwrap();
+    assert_eq!(visits.get(&rtx, "/").unwrap(), None);
+    assert_eq!(other.get(&rtx, "x").unwrap(), None);
+    drop(rtx);
+    assert!(committed.lock().unwrap().is_empty());
+
+    // Post-commit hooks only see writes to their tables.
+    store
+        .with_wtx(|wtx| {
+            visits.put(wtx, "/", &1)?;
+            other.put(wtx, "x", &1)
+        })
+        .unwrap();
+    assert_eq!(
+        *committed.lock().unwrap(),
+        [Write {
+            table: Arc::from("visits"),
+            key: b"/".to_vec(),
+            op: Op::Put(OwnedType::<u64>::encode(&1).into_owned()),
+        }]
+    );
+
+    assert!(store.observers().remove_hook(veto));
+    assert!(!store.observers().remove_hook(veto));
+    store.with_wtx(|wtx| visits.put(wtx, "/admin", &1)).unwrap();
+
+    vetoed
+}
+
+#[cfg(feature = "impl-mem")]
+mod mem {
+    use super::*;
+    use stor::db::mem::{MemError, MemStore};
+
+    #[test]
+    fn subscriptions_see_committed_changes_in_range() {
+        super::subscriptions_see_committed_changes_in_range(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn dropped_subscriptions_are_pruned() {
+        super::dropped_subscriptions_are_pruned(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn malformed_changes() {
+        super::malformed_changes(&Arc::new(MemStore::new()));
+    }
+
+    #[test]
+    fn vetoed_commits_are_aborted() {
+        let vetoed = super::vetoed_commits_are_aborted(&Arc::new(MemStore::new()));
+        assert!(matches!(vetoed, MemError::Vetoed(Veto(reason)) if reason.contains("admin")));
+    }
+}
+
+#[cfg(feature = "impl-rocks")]
+mod rocks {
+    use super::*;
+    use common::{rocks_options, TempDir};
+    use stor::db::rocks::{self, RockError, RockStore};
+
+    fn open(dir: &TempDir) -> Arc<RockStore> {
+        Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap())
+    }
+
+    #[test]
+    fn subscriptions_see_committed_changes_in_range() {
+        let dir = TempDir::new("observe");
+        super::subscriptions_see_committed_changes_in_range(&open(&dir));
+    }
+
+    #[test]
+    fn dropped_subscriptions_are_pruned() {
+        let dir = TempDir::new("observe");
+        super::dropped_subscriptions_are_pruned(&open(&dir));
+    }
+
+    #[test]
+    fn malformed_changes() {
+        let dir = TempDir::new("observe");
+        super::malformed_changes(&open(&dir));
+    }
+
+    #[test]
+    fn vetoed_commits_are_aborted() {
+        let dir = TempDir::new("observe");
+        let vetoed = super::vetoed_commits_are_aborted(&open(&dir));
+        assert!(matches!(vetoed, RockError::Vetoed(Veto(reason)) if reason.contains("admin")));
+    }
+}
@@ -0,0 +1,47 @@
+//! Read-only and secondary access to RocksDB databases used by another instance.
+#![cfg(feature = "impl-rocks")]
+mod common;
+
+use std::sync::Arc;
+
+use common::{rocks_options, TempDir};
+use stor::config::TableConfig;
+use stor::db::rocks::{self, RockError};
+use stor::types::{OwnedType, Str};
+use stor::Store;
+
+#[test]
+fn secondary_catches_up_with_open_transaction() {
+    let (dir, secondary) = (TempDir::new("primary"), TempDir::new("secondary"));
+    let cfg = TableConfig::default();
+
+    let primary = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+    let users = primary.typed::<Str, OwnedType<u64>>("users", &cfg).unwrap();
+    primary
+        .with_wtx(|wtx| users.put(wtx, "alice", &30))
+        .unwrap();
+
+    let reader = Arc::new(
+        rocks::open_secondary(
+            dir.path(),
+            secondary.path().join("logs"),
+            &rocks_options(),
+            [],
+        )
+        .unwrap(),
+    );
+    let read = reader.typed::<Str, OwnedType<u64>>("users", &cfg).unwrap();
+
+    primary.with_wtx(|wtx| users.put(wtx, "bob", &40)).unwrap();
+
+    // Catching up does not wait for read transactions, not even of the same thread.
+    let rtx = reader.rtx().unwrap();
+    assert_eq!(read.get(&rtx, "bob").unwrap(), None);
+    reader.try_catch_up_with_primary().unwrap();
+    assert_eq!(read.get(&rtx, "bob").unwrap(), Some(40));
+
+    assert!(matches!(
+        reader.with_wtx(|_| Ok(())),
+        Err(RockError::ReadOnly)
+    ));
+}
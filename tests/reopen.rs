@@ -0,0 +1,83 @@
+//! Table settings that RocksDB only applies when the database is opened, or a table created.
+#![cfg(feature = "impl-rocks")]
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{rocks_options, TempDir};
+use stor::config::TableConfig;
+use stor::db::rocks::{self, RockError};
+use stor::expiring::Expiring;
+use stor::merge::{Counter, MergeOperator};
+use stor::types::Str;
+use stor::Store;
+
+fn counters() -> TableConfig {
+    TableConfig {
+        merge: Some(MergeOperator::of::<Counter>()),
+        ..Default::default()
+    }
+}
+
+fn visit(store: &Arc<rocks::RockStore>, cfg: &TableConfig) -> Result<i64, RockError> {
+    let visits = store.typed::<Str, Counter>("visits", cfg)?;
+    store.with_wtx(|wtx| visits.merge(wtx, "/", &1))?;
+    store
+        .with_rtx(|rtx| visits.get(rtx, "/"))
+        .map(Option::unwrap_or_default)
+}
+
+#[test]
+fn merge_after_reopen() {
+    let dir = TempDir::new("reopen-merge");
+    let cfg = counters();
+
+    // Created at runtime, with the operator of its configuration.
+    let store = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+    assert_eq!(visit(&store, &cfg).unwrap(), 1);
+    drop(store);
+
+    // Reopened with the operator.
+    let store = Arc::new(rocks::open(dir.path(), &rocks_options(), [("visits", &cfg)]).unwrap());
+    assert_eq!(visit(&store, &cfg).unwrap(), 2);
+    drop(store);
+
+    // Reopened with default options, the operator can no longer be applied.
+    let store = Arc::new(rocks::open(dir.path(), &rocks_options(), []).unwrap());
+    assert!(matches!(
+        visit(&store, &cfg),
+        Err(RockError::Unconfigured { table, .. }) if table == "visits"
+    ));
+}
+
+#[test]
+fn expiring_after_reopen() {
+    let dir = TempDir::new("reopen-expiring");
+    let cfg = TableConfig::default();
+    let open = |tables: &[(&str, &TableConfig)]| {
+        let store =
+            Arc::new(rocks::open(dir.path(), &rocks_options(), tables.iter().copied()).unwrap());
+        let sessions = Expiring::<_, Str, Str>::open(&store, "sessions", &cfg)?;
+        let session = store.with_rtx(|rtx| sessions.get(rtx, "alice"))?;
+        Ok::<_, RockError>((store, sessions, session))
+    };
+
+    let (store, sessions, _) = open(&[]).unwrap();
+    store
+        .with_wtx(|wtx| sessions.put(wtx, "alice", "token", Duration::from_secs(3600)))
+        .unwrap();
+    drop((store, sessions));
+
+    let expiring = TableConfig {
+        expiring: true,
+        ..Default::default()
+    };
+    let (_, _, session) = open(&[("sessions", &expiring)]).unwrap();
+    assert_eq!(session.as_deref(), Some("token"));
+
+    assert!(matches!(
+        open(&[]),
+        Err(RockError::Unconfigured { table, .. }) if table == "sessions"
+    ));
+}
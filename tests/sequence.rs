@@ -0,0 +1,107 @@
+//! Persistent sequences and auto-increment keys.
+mod common;
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::sequence::Sequence;
+use stor::types::{OwnedType, Str};
+use stor::Store;
+
+#[cfg(feature = "impl-mem")]
+#[test]
+fn next_is_gapless() {
+    let store = Arc::new(stor::db::mem::MemStore::new());
+    let ids = Sequence::new(&store, "ids").unwrap();
+
+    assert_eq!(store.with_wtx(|wtx| ids.next(wtx)).unwrap(), 1);
+
+    // IDs of transactions that do not commit are handed out again.
+    let mut wtx = store.wtx().unwrap();
+    assert_eq!(ids.next(&mut wtx).unwrap(), 2);
+    drop(wtx);
+
+    let reopened = Sequence::new(&store, "ids").unwrap();
+    assert_eq!(store.with_wtx(|wtx| reopened.next(wtx)).unwrap(), 2);
+}
+
+#[cfg(feature = "impl-mem")]
+#[test]
+fn allocate_reserves_blocks() {
+    let store = Arc::new(stor::db::mem::MemStore::new());
+    let ids = Sequence::new(&store, "ids").unwrap().block_size(10);
+
+    let allocated = std::thread::scope(|scope| {
+        let threads = (0..4)
+            .map(|_| scope.spawn(|| (0..25).map(|_| ids.allocate().unwrap()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect::<BTreeSet<_>>()
+    });
+    assert_eq!(allocated, (1..=100).collect());
+
+    // A new instance continues after the last reserved block.
+    let reopened = Sequence::new(&store, "ids").unwrap();
+    assert_eq!(reopened.allocate().unwrap(), 101);
+}
+
+/// Concurrent inserts get distinct IDs.
+fn insert_auto<S: Store>(store: Arc<S>)
+where
+    S::Error: std::fmt::Debug,
+{
+    let names = store
+        .typed::<OwnedType<u64>, Str>("names", &TableConfig::default())
+        .unwrap();
+
+    let inserted = std::thread::scope(|scope| {
+        let threads = (0..4)
+            .map(|t| {
+                let (store, names) = (&store, &names);
+                scope.spawn(move || {
+                    (0..25)
+                        .map(|_| {
+                            store
+                                .with_wtx(|wtx| names.insert_auto(wtx, &format!("thread{t}")))
+                                .unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect::<BTreeSet<_>>()
+    });
+    assert_eq!(inserted.len(), 100);
+
+    let stored = store
+        .with_rtx(|rtx| {
+            names
+                .range(rtx, &..)?
+                .map(|item| item.map(|(id, _)| id))
+                .collect::<Result<BTreeSet<_>, _>>()
+        })
+        .unwrap();
+    assert_eq!(stored, inserted);
+}
+
+#[cfg(feature = "impl-mem")]
+#[test]
+fn mem_insert_auto() {
+    insert_auto(Arc::new(stor::db::mem::MemStore::new()));
+}
+
+#[cfg(feature = "impl-rocks")]
+#[test]
+fn rocks_insert_auto() {
+    let dir = common::TempDir::new("sequence");
+    let opts = common::rocks_options();
+    insert_auto(Arc::new(
+        stor::db::rocks::open(dir.path(), &opts, []).unwrap(),
+    ));
+}
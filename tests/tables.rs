@@ -0,0 +1,75 @@
+//! Ownership tests for [`stor::Tables`]. These run against the in-memory backend, so they can
+//! also be checked for undefined behavior with `cargo +nightly miri test --test tables`.
+#![cfg(feature = "impl-mem")]
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::db::mem::MemStore;
+use stor::types::{OwnedType, Str};
+use stor::{Store, Tables, Typed};
+
+struct DB<S: Store> {
+    users: Typed<S, Str, OwnedType<u64>>,
+}
+
+fn tables() -> Tables<MemStore, DB<MemStore>> {
+    Tables::new(MemStore::new(), &TableConfig::default(), |store, cfg| {
+        Ok(DB {
+            users: store.typed("users", cfg)?,
+        })
+    })
+    .unwrap()
+}
+
+#[test]
+fn read_write() {
+    let db = tables();
+
+    db.store
+        .with_wtx(|wtx| db.users.put(wtx, "alice", &1))
+        .unwrap();
+
+    let alice = db.store.with_rtx(|rtx| db.users.get(rtx, "alice")).unwrap();
+    assert_eq!(alice, Some(1));
+}
+
+#[test]
+fn tables_outlive_container() {
+    let db = tables();
+    let store = db.store.clone();
+    let users = db.users.clone();
+    drop(db);
+
+    store.with_wtx(|wtx| users.put(wtx, "bob", &2)).unwrap();
+
+    let bob = store.with_rtx(|rtx| users.get(rtx, "bob")).unwrap();
+    assert_eq!(bob, Some(2));
+}
+
+#[test]
+fn store_released_with_last_handle() {
+    let db = tables();
+    let store = Arc::downgrade(&db.store);
+    let users = db.users.clone();
+
+    drop(db);
+    assert!(store.upgrade().is_some());
+
+    drop(users);
+    assert!(store.upgrade().is_none());
+}
+
+#[test]
+fn uncommitted_writes_are_discarded() {
+    let db = tables();
+
+    {
+        let mut wtx = db.store.wtx().unwrap();
+        db.users.put(&mut wtx, "carol", &3).unwrap();
+        assert_eq!(db.users.get(&wtx, "carol").unwrap(), Some(3));
+    }
+
+    let carol = db.store.with_rtx(|rtx| db.users.get(rtx, "carol")).unwrap();
+    assert_eq!(carol, None);
+}
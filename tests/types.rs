@@ -0,0 +1,82 @@
+//! Ordering of keys in the built-in formats.
+#![cfg(feature = "impl-mem")]
+
+use std::sync::Arc;
+
+use stor::config::TableConfig;
+use stor::db::mem::MemStore;
+use stor::format::{DFormat, EFormat};
+use stor::types::{BigEndian, Empty};
+use stor::Store;
+
+/// Puts `keys` in a shuffled order, and checks that ranges return them sorted.
+fn sorted<F, T>(keys: &[T])
+where
+    F: for<'a> EFormat<'a, EItem = T> + DFormat<DItem = T>,
+    T: Ord + Copy + std::fmt::Debug,
+{
+    let store = Arc::new(MemStore::new());
+    let table = store
+        .typed::<F, Empty>("keys", &TableConfig::default())
+        .unwrap();
+
+    store
+        .with_wtx(|wtx| {
+            for key in keys.iter().rev().chain(keys.iter().step_by(2)) {
+                table.put(wtx, key, &())?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let mut expected = keys.to_vec();
+    expected.sort();
+    expected.dedup();
+
+    let stored = store
+        .with_rtx(|rtx| {
+            table
+                .range(rtx, &..)?
+                .map(|item| Ok(item?.0))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap();
+    assert_eq!(stored, expected);
+
+    let middle = expected[expected.len() / 2];
+    let stored = store
+        .with_rtx(|rtx| {
+            table
+                .rev_range(rtx, &(..middle))?
+                .map(|item| Ok(item?.0))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap();
+    let mut below = expected[..expected.len() / 2].to_vec();
+    below.reverse();
+    assert_eq!(stored, below);
+}
+
+#[test]
+fn big_endian_unsigned() {
+    sorted::<BigEndian<u16>, _>(&[0, 1, 255, 256, 0x0100, 0xFF00, u16::MAX]);
+    sorted::<BigEndian<u64>, _>(&[0, 1, 255, 256, 1 << 32, u64::MAX - 1, u64::MAX]);
+    sorted::<BigEndian<u128>, _>(&[0, 1, 256, 1 << 64, u128::MAX]);
+}
+
+#[test]
+fn big_endian_signed() {
+    sorted::<BigEndian<i8>, _>(&[i8::MIN, -1, 0, 1, i8::MAX]);
+    sorted::<BigEndian<i64>, _>(&[i64::MIN, -256, -255, -1, 0, 1, 255, 256, i64::MAX]);
+    sorted::<BigEndian<i128>, _>(&[i128::MIN, -1, 0, 1, i128::MAX]);
+}
+
+#[test]
+fn big_endian_rejects_other_lengths() {
+    assert_eq!(BigEndian::<u32>::decode(&[1, 2, 3]), None);
+    assert_eq!(BigEndian::<i64>::decode(&[0; 16]), None);
+    assert_eq!(
+        BigEndian::<i32>::decode(&BigEndian::<i32>::encode(&-7)),
+        Some(-7)
+    );
+}